@@ -0,0 +1,65 @@
+use std::hint::black_box;
+
+use chip_8_emulator::cpu::CPU;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+/// Instructions executed per benchmark iteration.
+const STEPS: u64 = 10_000;
+
+/// Register arithmetic, logic and skips in an endless loop.
+const ARITHMETIC: [u8; 24] = [
+    0x60, 0x11, // V0 = 0x11
+    0x61, 0x05, // V1 = 0x05
+    0x80, 0x14, // V0 += V1
+    0x80, 0x15, // V0 -= V1
+    0x82, 0x06, // V2 = V0 >> 1
+    0x83, 0x01, // V3 |= V0
+    0x83, 0x23, // V3 ^= V2
+    0x71, 0x01, // V1 += 1
+    0x31, 0x00, // skip if V1 == 0
+    0x12, 0x04, // jump 0x204
+    0xC4, 0xFF, // V4 = random
+    0x12, 0x00, // jump 0x200
+];
+
+/// Font sprites drawn across the screen in an endless loop.
+const DRAW: [u8; 14] = [
+    0x60, 0x00, // V0 = 0
+    0x61, 0x00, // V1 = 0
+    0xF2, 0x29, // I = font sprite for V2
+    0xD0, 0x15, // draw 5 rows at (V0, V1)
+    0x70, 0x05, // V0 += 5
+    0x72, 0x01, // V2 += 1
+    0x12, 0x04, // jump 0x204
+];
+
+fn run(c: &mut Criterion, name: &str, program: &[u8]) {
+    let mut group = c.benchmark_group("dispatch");
+    group.throughput(Throughput::Elements(STEPS));
+
+    group.bench_function(name, |b| {
+        let mut cpu = CPU::with_seed(0);
+        cpu.load_rom(program)
+            .expect("Could not load benchmark ROM!");
+
+        b.iter(|| {
+            for _ in 0..STEPS {
+                cpu.step().expect("Benchmark ROM failed!");
+            }
+            black_box(cpu.program_counter())
+        })
+    });
+
+    group.finish();
+}
+
+fn arithmetic(c: &mut Criterion) {
+    run(c, "arithmetic", &ARITHMETIC);
+}
+
+fn draw(c: &mut Criterion) {
+    run(c, "draw", &DRAW);
+}
+
+criterion_group!(benches, arithmetic, draw);
+criterion_main!(benches);
@@ -0,0 +1,177 @@
+use log::{info, warn};
+
+use crate::{
+    cpu::{RomError, CPU},
+    quirks::Quirks,
+    ram::RAM_SIZE,
+    rom::Platform,
+};
+
+#[derive(Debug)]
+pub enum BuildError {
+    /// The RAM has a fixed size of `RAM_SIZE` bytes, other sizes are not supported yet.
+    UnsupportedRamSize(usize),
+    Rom(RomError),
+}
+
+impl From<RomError> for BuildError {
+    fn from(error: RomError) -> Self {
+        BuildError::Rom(error)
+    }
+}
+
+/// Collects the options for a `CPU` and applies them in one go with `build`, instead of
+/// calling `CPU::new` followed by the setters.
+#[derive(Debug, Clone)]
+pub struct CpuBuilder {
+    quirks: Quirks,
+    clock_speed: Option<f64>,
+    seed: Option<u64>,
+    ram_size: usize,
+    platform: Platform,
+    rom: Option<Vec<u8>>,
+}
+impl CpuBuilder {
+    pub fn new() -> Self {
+        CpuBuilder {
+            quirks: Quirks::default(),
+            clock_speed: None,
+            seed: None,
+            ram_size: RAM_SIZE,
+            platform: Platform::Chip8,
+            rom: None,
+        }
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Instructions per second, the CPU's default is kept if not set.
+    pub fn clock_speed(mut self, clock_speed: f64) -> Self {
+        self.clock_speed = Some(clock_speed);
+        self
+    }
+
+    /// Makes the Cxkk random numbers reproducible, see `CPU::with_seed`.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Bytes of RAM. Only `RAM_SIZE` is supported for now, `build` rejects anything else.
+    pub fn ram_size(mut self, ram_size: usize) -> Self {
+        self.ram_size = ram_size;
+        self
+    }
+
+    /// Decides where the ROM is loaded and execution starts, 0x200 unless set.
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    /// ROM to load at the platform's program start.
+    pub fn rom(mut self, data: &[u8]) -> Self {
+        self.rom = Some(data.to_vec());
+        self
+    }
+
+    pub fn build(self) -> Result<CPU, BuildError> {
+        if self.ram_size != RAM_SIZE {
+            warn!(
+                "Cannot build a CPU with {} bytes of RAM, only {} are supported.",
+                self.ram_size, RAM_SIZE
+            );
+            return Err(BuildError::UnsupportedRamSize(self.ram_size));
+        };
+
+        info!("Building CPU.");
+
+        let mut cpu = match self.seed {
+            Some(seed) => CPU::with_seed(seed),
+            None => CPU::new(),
+        };
+
+        cpu.set_quirks(self.quirks);
+        if let Some(clock_speed) = self.clock_speed {
+            cpu.set_clock_speed(clock_speed);
+        };
+        cpu.set_platform(self.platform);
+        match self.rom {
+            Some(rom) => cpu.load_rom(&rom)?,
+            // Start at the platform's program start even without a ROM.
+            None => cpu.reset(),
+        };
+
+        Ok(cpu)
+    }
+}
+
+#[cfg(test)]
+mod builder_tests {
+    use super::*;
+    use crate::io::Read;
+
+    #[test]
+    fn test_build_with_options() {
+        // V0 = random & 0xFF
+        let rom = [0xC0, 0xFF];
+        let build = || {
+            CpuBuilder::new()
+                .quirks(Quirks::cosmac_vip())
+                .clock_speed(700.)
+                .seed(7)
+                .rom(&rom)
+                .build()
+                .unwrap()
+        };
+
+        let mut cpu = build();
+        assert_eq!(cpu.quirks(), Quirks::cosmac_vip());
+        assert_eq!(cpu.clock_speed(), 700.);
+        assert_eq!(cpu.ram().read_range(0x200, 2).unwrap(), &rom);
+
+        // The same seed gives the same random numbers.
+        let mut other = build();
+        cpu.step().unwrap();
+        other.step().unwrap();
+        assert_eq!(cpu.registers().read(0), other.registers().read(0));
+    }
+
+    #[test]
+    fn test_build_for_platform() {
+        let cpu = CpuBuilder::new()
+            .platform(Platform::Eti660)
+            .rom(&[0x00, 0xE0])
+            .build()
+            .unwrap();
+
+        assert_eq!(cpu.program_counter(), 0x600);
+        assert_eq!(cpu.ram().read_range(0x600, 2).unwrap(), &[0x00, 0xE0]);
+
+        let cpu = CpuBuilder::new()
+            .platform(Platform::Eti660)
+            .build()
+            .unwrap();
+        assert_eq!(cpu.platform(), Platform::Eti660);
+        assert_eq!(cpu.program_counter(), 0x600);
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let cpu = CpuBuilder::new().build().unwrap();
+        assert_eq!(cpu.quirks(), Quirks::default());
+        assert_eq!(cpu.clock_speed(), CPU::new().clock_speed());
+
+        assert!(matches!(
+            CpuBuilder::new().ram_size(0x10000).build(),
+            Err(BuildError::UnsupportedRamSize(0x10000))
+        ));
+        assert!(matches!(
+            CpuBuilder::new().rom(&[0x00]).build(),
+            Err(BuildError::Rom(RomError::TooSmall { size: 1 }))
+        ));
+    }
+}
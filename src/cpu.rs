@@ -1,19 +1,30 @@
 use std::{
-    ops::{Add, Sub},
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    sync::mpsc::{channel, Receiver, Sender},
     thread,
     time::{Duration, Instant},
 };
 
-use log::{info, trace};
-use rand::Rng;
+use flate2::read::GzDecoder;
+use log::{error, info, trace, warn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
+    instruction::Instruction,
     io::{MemoryError, Read, Write},
     keyboard::Keyboard,
-    ram::{Stack, RAM},
-    registers::{I, V},
-    screen::Screen,
+    quirks::Quirks,
+    ram::{Stack, RAM, RAM_SIZE},
+    registers::{Register, I, V},
+    rewind::RewindBuffer,
+    rom::Platform,
+    screen::{Screen, ScreenError, ScreenMode},
+    sound::PATTERN_SIZE,
     timer::{DelayTimer, SoundTimer},
+    undo::{UndoEntry, UndoStack},
 };
 
 const SPRITES: [u8; 80] = [
@@ -35,11 +46,277 @@ const SPRITES: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+/// SCHIP 8x10 font for the digits 0 to 9.
+const HIRES_SPRITES: [u8; 100] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+/// Default address of the font in RAM, where most interpreters put it.
+pub const DEFAULT_FONT_BASE: u16 = 0x50;
+/// Bytes taken by both fonts. The hi-res font is stored right after the regular font.
+const FONT_SIZE: usize = SPRITES.len() + HIRES_SPRITES.len();
+
+/// Rate at which `clock` runs frames and the timers count down.
+pub const FRAME_RATE: f64 = 60.;
+/// Default speed-up while turbo is held.
+pub const DEFAULT_TURBO_FACTOR: f64 = 4.;
+/// Default upper bound of instructions in one frame, see `CPU::set_max_instructions_per_frame`.
+pub const DEFAULT_MAX_INSTRUCTIONS_PER_FRAME: usize = 10_000;
+
+/// Address where programs are loaded and execution starts.
+const PROGRAM_START: u16 = 0x200;
+
+/// Largest ROM that fits between `PROGRAM_START` and the end of RAM.
+const MAX_ROM_SIZE: usize = RAM_SIZE - PROGRAM_START as usize;
+/// First two bytes of every gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+#[derive(Debug)]
+pub enum RomError {
+    Io(std::io::Error),
+    TooLarge {
+        size: usize,
+        max: usize,
+    },
+    /// The ROM does not hold a single full instruction.
+    TooSmall {
+        size: usize,
+    },
+    Memory(MemoryError),
+}
+
+impl From<std::io::Error> for RomError {
+    fn from(error: std::io::Error) -> Self {
+        RomError::Io(error)
+    }
+}
+
+impl From<MemoryError> for RomError {
+    fn from(error: MemoryError) -> Self {
+        RomError::Memory(error)
+    }
+}
+
+/// Reads a whole ROM from `reader`. Gzip-compressed ROMs are detected by their magic number and
+/// decompressed first.
+/// Fails with `RomError::TooLarge` without buffering more than one byte past the limit, and with
+/// `RomError::TooSmall` if there is not a single instruction.
+pub fn read_rom<R: std::io::Read>(reader: R) -> Result<Vec<u8>, RomError> {
+    let mut reader = BufReader::new(reader);
+
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        trace!("ROM is gzip-compressed.");
+        read_uncompressed_rom(GzDecoder::new(reader))
+    } else {
+        read_uncompressed_rom(reader)
+    }
+}
+
+fn read_uncompressed_rom<R: std::io::Read>(reader: R) -> Result<Vec<u8>, RomError> {
+    use std::io::Read as _;
+
+    let mut data = Vec::new();
+    reader
+        .take(MAX_ROM_SIZE as u64 + 1)
+        .read_to_end(&mut data)?;
+
+    check_rom_size(PROGRAM_START, data.len())?;
+
+    Ok(data)
+}
+
+/// Fails unless a ROM of `size` bytes holds an instruction and fits at `offset`.
+fn check_rom_size(offset: u16, size: usize) -> Result<(), RomError> {
+    let max = RAM_SIZE.saturating_sub(offset as usize);
+    if size > max {
+        return Err(RomError::TooLarge { size, max });
+    };
+
+    if size < 2 {
+        return Err(RomError::TooSmall { size });
+    };
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    /// The program counter does not point at a full instruction inside RAM, or an instruction
+    /// would access memory past the end of RAM starting at the given address.
+    OutOfBounds(u16),
+    /// The opcode is not a known instruction.
+    InvalidOpcode(u16),
+    /// Advancing the program counter moved it past the end of RAM.
+    ProgramCounterOutOfBounds(u16),
+    Memory(MemoryError),
+    Screen(ScreenError),
+}
+
+/// What `step` does when it meets an opcode that is not an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidOpcodePolicy {
+    /// Fail with `CpuError::InvalidOpcode` and pause.
+    #[default]
+    Halt,
+    /// Log a warning and continue with the next instruction.
+    Skip,
+    /// Treat the opcode as a no-op without logging it.
+    Nop,
+}
+
+/// Why the CPU stopped executing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltReason {
+    /// The program ran the SCHIP 00FD exit instruction.
+    Exit,
+    /// The program jumped to its own address with 1nnn, the usual way to end a CHIP-8 program.
+    /// The CPU idles, the screen and keyboard stay usable.
+    Idle,
+    Error(CpuError),
+}
+
+/// Sent to every `CPU::on_halt` receiver when an instruction fails or the program exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuHalted {
+    pub reason: HaltReason,
+    /// Address of the instruction that failed or exited.
+    pub program_counter: u16,
+}
+
+impl From<MemoryError> for CpuError {
+    fn from(error: MemoryError) -> Self {
+        CpuError::Memory(error)
+    }
+}
+
+impl From<ScreenError> for CpuError {
+    fn from(error: ScreenError) -> Self {
+        CpuError::Screen(error)
+    }
+}
+
+/// Counters for debugging, e.g. to check that every sprite drawn is erased again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CpuStats {
+    /// Executed instructions, including invalid ones the policy stepped over.
+    pub instruction_count: u64,
+    /// Executed Dxyn instructions.
+    pub draw_count: u64,
+    /// Dxyn instructions that erased at least one pixel.
+    pub collision_count: u64,
+    /// Return addresses currently on the stack.
+    pub stack_depth: usize,
+    /// Deepest the stack got, e.g. to spot runaway recursion before it overflows.
+    pub max_stack_depth: usize,
+}
+
+/// A change of a watched register, see `CPU::watch_register`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterChange {
+    pub register: Register,
+    pub old: u8,
+    pub new: u8,
+    /// Address of the instruction that changed the register.
+    pub program_counter: u16,
+}
+
+/// A RAM byte changed by an instruction, see `ExecutedInstruction`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryChange {
+    pub address: u16,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// One entry of the trace returned by `CPU::step_n_traced`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutedInstruction {
+    pub program_counter: u16,
+    pub opcode: u16,
+    /// None for invalid opcodes stepped over by the `InvalidOpcodePolicy`.
+    pub instruction: Option<Instruction>,
+    pub register_changes: Vec<RegisterChange>,
+    /// Old and new value of I if the instruction changed it.
+    pub i_change: Option<(u16, u16)>,
+    pub memory_changes: Vec<MemoryChange>,
+}
+
+/// Copy of everything a running program can observe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuState {
+    program_counter: u16,
+    ram: RAM,
+    stack: Stack,
+    sound_timer: u8,
+    delay_timer: u8,
+    v: V,
+    i: I,
+    rpl: [u8; 16],
+    screen: Screen,
+}
+
+/// Callback around an executed instruction, called with the opcode and its address.
+pub type ExecuteHook = Box<dyn FnMut(u16, u16)>;
+
 pub struct CPU {
     is_paused: bool,
+    // Set by 00FD, nothing is executed anymore until `reset`.
+    is_halted: bool,
+    // Set when 1nnn jumps to itself, nothing would change by executing further.
+    is_idle: bool,
+    rewind: Option<RewindBuffer>,
+    // Executions per full opcode, only counted while enabled.
+    opcode_histogram: Option<HashMap<u16, u64>>,
+    // Call sites per subroutine address, only recorded while `record_calls` is set.
+    record_calls: bool,
+    call_graph: HashMap<u16, Vec<u16>>,
+    undo: Option<UndoStack>,
+    halt_listeners: Vec<Sender<CpuHalted>>,
+    quirks: Quirks,
+    // Source of Cxkk random numbers.
+    rng: StdRng,
+    // Used by Cxkk instead of a random byte if set.
+    rng_override: Option<u8>,
+    stats: CpuStats,
+    // Reject program writes below PROGRAM_START.
+    interpreter_protection: bool,
+    invalid_opcode_policy: InvalidOpcodePolicy,
+    // Invalid opcodes stepped over by the policy.
+    unsupported_opcodes: HashSet<u16>,
+    strict: bool,
+    // Lenient behavior a program relied on while `strict` was set.
+    deviations: Vec<String>,
+    // Bit n set means register Vn is watched.
+    watched_registers: u16,
+    register_changes: Vec<RegisterChange>,
+    pre_execute_hook: Option<ExecuteHook>,
+    post_execute_hook: Option<ExecuteHook>,
 
     // Clock speed in Hz
     clock_speed: f64,
+    // While set, frames execute turbo_factor times as many instructions.
+    turbo: bool,
+    turbo_factor: f64,
+    max_instructions_per_frame: usize,
+    // Fx0A blocks the thread until a key is pressed instead of retrying on the next step.
+    blocking_key_wait: bool,
+    // Instructions owed to the next frame, scaled by FRAME_RATE to avoid rounding errors.
+    frame_cycle_remainder: f64,
+    // Set by Dxyn under the display_wait quirk, ends the current frame.
+    waiting_for_vblank: bool,
+    // Keypad state latched at the start of the running frame, None outside of `run_frame`.
+    latched_keys: Option<u16>,
+    // Where programs are loaded and execution starts after a reset.
+    platform: Platform,
     program_counter: u16,
     ram: RAM,
     stack: Stack,
@@ -47,524 +324,3836 @@ pub struct CPU {
     delay_timer: DelayTimer,
     v: V,
     i: I,
+    // SCHIP RPL user flags, saved and restored by Fx75/Fx85.
+    rpl: [u8; 16],
+    // Address of the regular font, the hi-res font follows it.
+    font_base: u16,
 
     screen: Screen,
     keyboard: Keyboard,
 }
 impl CPU {
     pub fn new() -> Self {
-        let mut ram = RAM::new();
-        ram.write_buf(0, &SPRITES)
-            .expect("Could not load SPRITES into RAM!");
+        Self::with_rng(StdRng::from_entropy())
+    }
 
-        trace!("Loaded sprites into RAM.");
+    /// Creates a CPU whose Cxkk random numbers are reproducible for the same `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        info!("Seeding RNG with {}.", seed);
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(rng: StdRng) -> Self {
+        let ram = Self::boot_ram(DEFAULT_FONT_BASE);
 
         info!("Creating new CPU instance.");
 
         CPU {
             is_paused: false,
+            is_halted: false,
+            is_idle: false,
+            rewind: None,
+            opcode_histogram: None,
+            record_calls: false,
+            call_graph: HashMap::new(),
+            undo: None,
+            halt_listeners: Vec::new(),
+            quirks: Quirks::default(),
+            rng,
+            rng_override: None,
+            stats: CpuStats::default(),
+            interpreter_protection: false,
+            invalid_opcode_policy: InvalidOpcodePolicy::default(),
+            unsupported_opcodes: HashSet::new(),
+            strict: false,
+            deviations: Vec::new(),
+            watched_registers: 0,
+            register_changes: Vec::new(),
+            pre_execute_hook: None,
+            post_execute_hook: None,
 
             clock_speed: 500.0,
-            program_counter: 0x200,
+            turbo: false,
+            turbo_factor: DEFAULT_TURBO_FACTOR,
+            max_instructions_per_frame: DEFAULT_MAX_INSTRUCTIONS_PER_FRAME,
+            blocking_key_wait: true,
+            frame_cycle_remainder: 0.,
+            waiting_for_vblank: false,
+            latched_keys: None,
+            platform: Platform::Chip8,
+            program_counter: PROGRAM_START,
             ram,
             stack: Stack::new(),
             sound_timer: SoundTimer::new(),
             delay_timer: DelayTimer::new(),
             v: V::new(),
             i: I::new(),
+            rpl: [0u8; 16],
+            font_base: DEFAULT_FONT_BASE,
 
             screen: Screen::new(),
             keyboard: Keyboard::new(),
         }
     }
 
-    pub fn load_rom(&mut self, data: &[u8]) -> Result<(), MemoryError> {
-        info!("Loading ROM.");
-        self.ram.write_buf(0x200, data)
-    }
+    /// RAM with nothing but the fonts loaded at `font_base`.
+    fn boot_ram(font_base: u16) -> RAM {
+        let mut ram = RAM::new();
+        Self::load_fonts(&mut ram, font_base).expect("Could not load SPRITES into RAM!");
 
-    fn cycle(&mut self) {
-        trace!("--- New Cycle ---");
-        trace!("Program Counter: {}", self.program_counter);
+        trace!("Loaded sprites into RAM.");
 
-        let opcode = (self.ram.read(self.program_counter).unwrap() as u16) << 8
-            | self.ram.read(self.program_counter + 1).unwrap() as u16;
+        ram
+    }
 
-        trace!("OPCODE: {}", opcode);
+    fn load_fonts(ram: &mut RAM, font_base: u16) -> Result<(), MemoryError> {
+        ram.write_buf(font_base, &SPRITES)?;
+        ram.write_buf(Self::hires_font_base(font_base), &HIRES_SPRITES)
+    }
 
-        self.execute_instruction(opcode);
+    fn hires_font_base(font_base: u16) -> u16 {
+        font_base + SPRITES.len() as u16
+    }
 
-        trace!("End of Cycle");
+    /// Address of the font sprite for digit 0, see `set_font_base`.
+    pub fn font_base(&self) -> u16 {
+        self.font_base
     }
 
-    fn execute_instruction(&mut self, opcode: u16) {
-        // Increment the program counter by 2 because one instruction is 2 bytes long (u16).
-        self.increment_program_counter();
+    /// Moves the fonts to `font_base`, the hi-res font following right after the regular one,
+    /// and points Fx29 and Fx30 there. Some interpreters put the font at 0 instead of 0x50.
+    /// The fonts are written right away and the old copy is left in RAM until the next reset.
+    /// Fails if the fonts do not fit into RAM, which leaves everything unchanged.
+    pub fn set_font_base(&mut self, font_base: u16) -> Result<(), CpuError> {
+        info!("Setting font base to {:#05X}.", font_base);
 
-        let x = ((opcode & 0x0F00) >> 8) as u8;
-        let y = ((opcode & 0x00F0) >> 4) as u8;
+        if font_base as usize + FONT_SIZE > RAM_SIZE {
+            return Err(MemoryError::OutOfBounds.into());
+        };
 
-        // match instructions
-        match opcode & 0xF000 {
-            0x0000 => match opcode {
-                0x00E0 => {
-                    trace!("Clearing screen.");
-                    self.screen.clear()
-                }
-                0x00EE => {
-                    self.program_counter = self.stack.pop().expect("Could not pop off of stack!");
-                    trace!(
-                        "Return from a subroutine. New program counter: {}",
-                        self.program_counter
-                    );
-                }
-                _ => {
-                    // Instruction 0nnn
+        Self::load_fonts(&mut self.ram, font_base)?;
+        self.font_base = font_base;
 
-                    let nnn = opcode & 0xFFF;
+        Ok(())
+    }
 
-                    trace!("Set ProgramCounter to {}", nnn);
+    /// Puts the machine back into its power-on state, e.g. before loading another ROM.
+    /// Quirks, clock speed, the platform, the RNG, RPL flags and halt listeners are kept. Rewind
+    /// and undo stay enabled but lose their history.
+    pub fn reset(&mut self) {
+        info!("Resetting CPU.");
 
-                    self.program_counter = nnn;
-                }
-            },
-            0x1000 => {
-                self.program_counter = opcode & 0xFFF;
-                trace!("Jump to {}", self.program_counter);
-            }
-            0x2000 => {
-                self.stack.push(self.program_counter).expect(&format!(
-                    "Could not push ProgramCounter({}) on to the stack!",
-                    self.program_counter
-                ));
+        self.is_paused = false;
+        self.is_halted = false;
+        self.is_idle = false;
+        self.stats = CpuStats::default();
+        self.unsupported_opcodes.clear();
+        self.deviations.clear();
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.clear();
+        };
+        if let Some(undo) = self.undo.as_mut() {
+            undo.clear();
+        };
 
-                let nnn = opcode & 0xFFF;
-                self.program_counter = nnn;
-                trace!("Call subroutine at {}", nnn);
-            }
-            0x3000 => {
-                trace!("Skip next instruction if V({}) == KK.", x);
-                let vx = self.v.read(x).expect(&format!("Could not read V({})!", x));
-                let kk = (opcode & 0xFF) as u8;
+        self.frame_cycle_remainder = 0.;
+        self.waiting_for_vblank = false;
+        self.program_counter = self.platform.program_start();
+        self.ram = Self::boot_ram(self.font_base);
+        self.stack = Stack::new();
+        self.sound_timer.write(0);
+        self.sound_timer.clear_pattern();
+        self.delay_timer.write(0);
+        self.v = V::new();
+        self.i = I::new();
+        self.screen = Screen::new();
+        self.screen.set_clip_sprites(self.quirks.clip_sprites);
+    }
 
-                if vx == kk {
-                    trace!("Skipping next instruction.");
-                    self.increment_program_counter();
-                };
-            }
-            0x4000 => {
-                trace!("Skip next instruction if V({}) != KK.", x);
-                let vx = self.v.read(x).expect(&format!("Could not read V({})!", x));
-                let kk = (opcode & 0xFF) as u8;
+    /// Replaces the whole RAM with `image`, e.g. to set up a precise test scenario.
+    /// This overwrites the fonts and the rest of the interpreter area below 0x200 as well, so
+    /// Fx29 and Fx30 only find a font if `image` has one at `font_base`. The program counter and
+    /// all registers are left alone.
+    pub fn load_memory_image(&mut self, image: &[u8; RAM_SIZE]) {
+        info!("Loading a {} byte memory image.", RAM_SIZE);
 
-                if vx != kk {
-                    trace!("Skipping next instruction.");
-                    self.increment_program_counter();
-                };
-            }
-            0x5000 => {
-                trace!("Skip next instruction if V({}) == V({}).", x, y);
-                let vx = self.v.read(x).expect(&format!("Could not read V({})!", x));
-                let vy = self.v.read(y).expect(&format!("Could not read V({})!", x));
+        self.ram
+            .write_buf(0, image)
+            .expect("Could not load the memory image into RAM!");
+    }
 
-                if vx == vy {
-                    trace!("Skipping instruction.");
-                    self.increment_program_counter();
-                };
-            }
-            0x6000 => {
-                let kk = (opcode & 0xFF) as u8;
-                trace!("Setting V({}) to {}", x, kk);
-                self.v
-                    .write(x, kk)
-                    .expect(&format!("Could not write {} to V({})", kk, x));
-            }
-            0x7000 => {
-                let vx = self.v.read(x).expect(&format!("Could not read V({})!", x));
-                let kk = (opcode & 0xFF) as u8;
-                trace!("Set V({}) to {} + {}", x, vx, kk);
-                self.v.write(x, vx.wrapping_add(kk)).expect(&format!(
-                    "Could not write {} to V({})!",
-                    vx as u16 + kk as u16,
-                    x
-                ));
-            }
-            0x8000 => match opcode & 0xF {
-                0x0 => {
-                    trace!("Set V({}) to V({})", x, y);
-                    self.v
-                        .write(
-                            x,
-                            self.v.read(y).expect(&format!("Could not read V({})!", y)),
-                        )
-                        .expect(&format!("Could not write V({}) to V({})!", y, x))
-                }
-                0x1 => {
-                    trace!("Set V({}) to V({}) | V({})", x, x, y);
-                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
-                    let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
-                    self.v.write(x, vx | vy).expect(&format!(
-                        "Could not write {} to V({})",
-                        vx | vy,
-                        x
-                    ));
-                }
-                0x2 => {
-                    trace!("Set V({}) to V({}) & V({})", x, x, y);
-                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
-                    let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
-                    self.v.write(x, vx & vy).expect(&format!(
-                        "Could not write {} to V({})",
-                        vx & vy,
-                        x
-                    ));
-                }
-                0x3 => {
-                    trace!("Set V({}) to V({}) ^ V({})", x, x, y);
-                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
-                    let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
-                    self.v.write(x, vx ^ vy).expect(&format!(
-                        "Could not write {} to V({})",
-                        vx ^ vy,
-                        x
-                    ));
-                }
-                0x4 => {
-                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
-                    let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
+    /// Loads `data` at the platform's program start, 0x200 unless set, and starts execution
+    /// there.
+    pub fn load_rom(&mut self, data: &[u8]) -> Result<(), RomError> {
+        self.load_rom_at(self.platform.program_start(), data)
+    }
 
-                    let result = vx.wrapping_add(vy);
+    /// Loads `data` at `offset` and starts execution there, e.g. 0x600 for the ETI-660.
+    /// ROMs shorter than one instruction or running past the end of RAM are rejected, odd-length
+    /// ROMs only log a warning.
+    pub fn load_rom_at(&mut self, offset: u16, data: &[u8]) -> Result<(), RomError> {
+        info!("Loading ROM at {:#05X}.", offset);
 
-                    let carry = if vx as u16 + vy as u16 > u8::MAX as u16 {
-                        1
-                    } else {
-                        0
-                    };
+        if offset as usize >= RAM_SIZE {
+            return Err(MemoryError::OutOfBounds.into());
+        };
 
-                    trace!(
-                        "Set V({}) = V({}) + V({}), set V(0xF) = Carry {}",
-                        x,
-                        x,
-                        y,
-                        carry
-                    );
+        check_rom_size(offset, data.len())?;
 
-                    // Set carry
-                    self.v
-                        .write(0xF, carry)
-                        .expect(&format!("Could not write carry to V({})!", 0xF));
+        if !data.len().is_multiple_of(2) {
+            warn!("ROM has an odd length of {} bytes.", data.len());
+        };
 
-                    self.v.write(x, result as u8).expect(&format!(
-                        "Could not write sum of {} and {} to V({})!",
-                        vx, vy, x
-                    ));
-                }
-                0x5 => {
-                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
-                    let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
+        self.ram.write_buf(offset, data)?;
+        self.program_counter = offset;
 
-                    let borrow = if vx >= vy { 1 } else { 0 };
+        Ok(())
+    }
 
-                    trace!(
-                        "Set V({}) = V({}) {} - V({}) {}, set V(0xF) = Borrow {}",
-                        x,
-                        x,
-                        vx,
-                        y,
-                        vy,
-                        borrow
-                    );
+    /// Loads `data` where `platform` expects programs and starts execution there. The platform is
+    /// kept for later resets and reloads, see `set_platform`.
+    pub fn load_rom_for(&mut self, platform: Platform, data: &[u8]) -> Result<(), RomError> {
+        info!("Loading ROM for {:?}.", platform);
+        self.load_rom_at(platform.program_start(), data)?;
+        self.platform = platform;
 
-                    let result = vx.wrapping_sub(vy);
+        Ok(())
+    }
 
-                    // Set carry
-                    self.v
-                        .write(0xF, borrow)
-                        .expect(&format!("Could not write carry to V({})!", 0xF));
+    /// Resets the CPU and loads `data` at the platform's program start, so the new program starts
+    /// from a clean state.
+    /// `load_rom` on the other hand only overwrites the ROM bytes.
+    /// A ROM that is too small or too large is rejected before the reset, so the running program
+    /// is left untouched.
+    pub fn reload_rom(&mut self, data: &[u8]) -> Result<(), RomError> {
+        check_rom_size(self.platform.program_start(), data.len())?;
 
-                    self.v.write(x, result as u8).expect(&format!(
-                        "Could not write sum of {} and {} to V({})!",
-                        vx, vy, x
-                    ));
-                }
-                0x6 => {
-                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x)) & 0x1;
+        self.reset();
+        self.load_rom(data)
+    }
 
-                    trace!("Set V({}) = V({}) SHR 1", x, x);
+    /// Reads a ROM from any reader and loads it into RAM, see `read_rom`.
+    pub fn load_rom_from_reader<R: std::io::Read>(&mut self, reader: R) -> Result<(), RomError> {
+        let data = read_rom(reader)?;
+        self.load_rom(&data)
+    }
 
-                    self.v
-                        .write(0xF, vx)
-                        .expect(&format!("Could not write {} to V({})", vx, x));
-                    self.v
-                        .write(
-                            x,
-                            self.v.read(x).expect(&format!("Could not read V({})!", x)) >> 1,
-                        )
-                        .expect(&format!("Could not write to V({})", x));
-                }
-                0x7 => {
-                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
-                    let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
+    /// Reads the ROM at `path` and loads it into RAM.
+    pub fn load_rom_from_path<P: AsRef<Path>>(&mut self, path: P) -> Result<(), RomError> {
+        info!("Reading ROM from {}.", path.as_ref().display());
+        self.load_rom_from_reader(File::open(path)?)
+    }
 
-                    let borrow = if vy > vx { 1 } else { 0 };
+    /// Reads the ROM at `path` and restarts with it like `reload_rom`, e.g. for a file dropped
+    /// onto the window. If the file cannot be read or is no usable ROM, the error is logged and
+    /// the running program is left untouched.
+    pub fn reload_rom_from_path<P: AsRef<Path>>(&mut self, path: P) -> Result<(), RomError> {
+        info!("Reloading ROM from {}.", path.as_ref().display());
 
-                    trace!(
-                        "Set V({}) = V({}) - V({}), set V(0xF) = Borrow {}",
-                        x,
-                        x,
-                        y,
-                        borrow
-                    );
+        File::open(&path)
+            .map_err(RomError::from)
+            .and_then(read_rom)
+            .and_then(|data| self.reload_rom(&data))
+            .inspect_err(|error| {
+                error!(
+                    "Could not load ROM {}: {:?}",
+                    path.as_ref().display(),
+                    error
+                )
+            })
+    }
 
-                    self.v
-                        .write(0xF, borrow)
-                        .expect(&format!("Could not write {} to V({})!", borrow, 0xF));
+    /// Freezes execution. `step` and `clock` keep running but no instruction is executed.
+    pub fn pause(&mut self) {
+        info!("Pausing CPU.");
+        self.is_paused = true;
+    }
+
+    /// Resumes execution after `pause`.
+    pub fn resume(&mut self) {
+        info!("Resuming CPU.");
+        self.is_paused = false;
+    }
+
+    /// Toggles between paused and running. Meant to be bound to a key by the front-end.
+    pub fn toggle_pause(&mut self) {
+        if self.is_paused {
+            self.resume();
+        } else {
+            self.pause();
+        };
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    /// Returns true once the program exited with 00FD.
+    pub fn is_halted(&self) -> bool {
+        self.is_halted
+    }
+
+    /// Returns true while the program sits in a jump to itself.
+    pub fn is_idle(&self) -> bool {
+        self.is_idle
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        info!("Setting quirks to {:?}", quirks);
+        self.quirks = quirks;
+        self.screen.set_clip_sprites(quirks.clip_sprites);
+    }
+
+    /// The general purpose registers, e.g. for tooling to inspect them with `V::get`.
+    pub fn registers(&self) -> &V {
+        &self.v
+    }
+
+    pub fn registers_mut(&mut self) -> &mut V {
+        &mut self.v
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Makes the CPU log a warning and record a deviation whenever a program relies on lenient
+    /// behavior that a stricter interpreter might not have: writes into the interpreter area,
+    /// 0nnn machine code calls, I leaving the 12-bit address space and invalid opcodes that the
+    /// policy steps over. Meant for ROM authors checking portability, independent of the quirks.
+    pub fn set_strict(&mut self, enabled: bool) {
+        info!("Setting strict mode to {}", enabled);
+        self.strict = enabled;
+    }
+
+    /// What the program did that a strict CHIP-8 would not allow, oldest first. Only recorded
+    /// in strict mode and cleared on reset.
+    pub fn deviations(&self) -> &[String] {
+        &self.deviations
+    }
+
+    pub fn rng_override(&self) -> Option<u8> {
+        self.rng_override
+    }
+
+    /// Makes Cxkk use `value & kk` instead of a random byte, so programs depending on it can be
+    /// tested without picking a seed. `None` goes back to random numbers.
+    pub fn set_rng_override(&mut self, value: Option<u8>) {
+        info!("Setting RNG override to {:?}", value);
+        self.rng_override = value;
+    }
+
+    pub fn interpreter_protection(&self) -> bool {
+        self.interpreter_protection
+    }
+
+    /// When enabled, Fx33 and Fx55 fail with `MemoryError::Protected` instead of writing below
+    /// 0x200, where the interpreter and the fonts live. Off by default since some ROMs do it on
+    /// purpose. Loading ROMs and host writes through `ram_mut` are not affected.
+    pub fn set_interpreter_protection(&mut self, enabled: bool) {
+        info!("Setting interpreter protection to {}", enabled);
+        self.interpreter_protection = enabled;
+    }
+
+    /// Records every change of `register` made by an executed instruction in
+    /// `register_changes`, like a watchpoint in a debugger.
+    pub fn watch_register(&mut self, register: Register) {
+        info!("Watching {:?}", register);
+        self.watched_registers |= 1 << register as u16;
+    }
+
+    pub fn unwatch_register(&mut self, register: Register) {
+        info!("No longer watching {:?}", register);
+        self.watched_registers &= !(1 << register as u16);
+    }
+
+    /// Changes of the watched registers, oldest first. They are kept until
+    /// `clear_register_changes`.
+    pub fn register_changes(&self) -> &[RegisterChange] {
+        &self.register_changes
+    }
+
+    pub fn clear_register_changes(&mut self) {
+        self.register_changes.clear();
+    }
+
+    pub fn invalid_opcode_policy(&self) -> InvalidOpcodePolicy {
+        self.invalid_opcode_policy
+    }
+
+    /// Chooses whether invalid opcodes halt the CPU or are stepped over, e.g. to explore
+    /// partially broken ROMs.
+    pub fn set_invalid_opcode_policy(&mut self, policy: InvalidOpcodePolicy) {
+        info!("Setting invalid opcode policy to {:?}", policy);
+        self.invalid_opcode_policy = policy;
+    }
+
+    /// Every invalid opcode the `Skip` or `Nop` policy stepped over since the last reset, as a
+    /// quick compatibility report for a ROM.
+    pub fn unsupported_opcodes(&self) -> &HashSet<u16> {
+        &self.unsupported_opcodes
+    }
+
+    /// Calls `hook` with the opcode and program counter before every instruction executes,
+    /// e.g. for tracers and debuggers. Replaces the previous hook.
+    pub fn set_pre_execute_hook(&mut self, hook: ExecuteHook) {
+        self.pre_execute_hook = Some(hook);
+    }
+
+    /// Calls `hook` with the opcode and its address after every instruction that executed
+    /// without an error. Replaces the previous hook.
+    pub fn set_post_execute_hook(&mut self, hook: ExecuteHook) {
+        self.post_execute_hook = Some(hook);
+    }
+
+    pub fn clear_execute_hooks(&mut self) {
+        self.pre_execute_hook = None;
+        self.post_execute_hook = None;
+    }
+
+    pub fn stats(&self) -> CpuStats {
+        CpuStats {
+            stack_depth: self.stack.depth(),
+            ..self.stats
+        }
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = CpuStats::default();
+    }
+
+    /// Starts counting how often each opcode executes, see `opcode_histogram`.
+    pub fn enable_opcode_histogram(&mut self) {
+        info!("Enabling the opcode histogram.");
+        self.opcode_histogram.get_or_insert_with(HashMap::new);
+    }
+
+    /// Stops counting opcodes and drops the counts.
+    pub fn disable_opcode_histogram(&mut self) {
+        self.opcode_histogram = None;
+    }
+
+    /// Number of times each full opcode executed since the histogram was enabled.
+    /// Empty while it is disabled.
+    pub fn opcode_histogram(&self) -> HashMap<u16, u64> {
+        self.opcode_histogram.clone().unwrap_or_default()
+    }
+
+    /// Starts recording which addresses call which subroutines, see `call_graph`.
+    pub fn enable_call_graph(&mut self) {
+        info!("Enabling the call graph.");
+        self.record_calls = true;
+    }
+
+    /// Stops recording calls and drops the call graph.
+    pub fn disable_call_graph(&mut self) {
+        self.record_calls = false;
+        self.call_graph.clear();
+    }
+
+    /// Maps every subroutine address called by 2nnn since the call graph was enabled to the
+    /// addresses of the CALL instructions that reached it, in the order they were first seen.
+    /// Empty while it is disabled.
+    pub fn call_graph(&self) -> &HashMap<u16, Vec<u16>> {
+        &self.call_graph
+    }
+
+    /// The index register.
+    pub fn index_register(&self) -> &I {
+        &self.i
+    }
+
+    pub fn index_register_mut(&mut self) -> &mut I {
+        &mut self.i
+    }
+
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Changes where `load_rom`, `reload_rom` and `reset` put the program, e.g. 0x600 for the
+    /// ETI-660. The running program is not moved.
+    pub fn set_platform(&mut self, platform: Platform) {
+        info!("Setting platform to {:?}.", platform);
+        self.platform = platform;
+    }
+
+    /// Moves execution to `program_counter`, e.g. for a debugger's "run to cursor".
+    /// Fails with `CpuError::ProgramCounterOutOfBounds` unless a whole instruction fits there.
+    /// Odd addresses are allowed like everywhere else but logged, since they are usually a
+    /// mistake.
+    pub fn set_program_counter(&mut self, program_counter: u16) -> Result<(), CpuError> {
+        if program_counter as usize + 1 >= RAM_SIZE {
+            return Err(CpuError::ProgramCounterOutOfBounds(program_counter));
+        };
+
+        if !program_counter.is_multiple_of(2) {
+            warn!(
+                "Setting the program counter to odd address {:#05X}",
+                program_counter
+            );
+        };
+
+        trace!("Set Program Counter to {}", program_counter);
+        self.program_counter = program_counter;
+
+        Ok(())
+    }
+
+    pub fn ram(&self) -> &RAM {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut RAM {
+        &mut self.ram
+    }
+
+    pub fn stack(&self) -> &Stack {
+        &self.stack
+    }
+
+    pub fn delay_timer(&self) -> &DelayTimer {
+        &self.delay_timer
+    }
+
+    /// The RPL user flags, e.g. for a front-end to persist high scores between sessions.
+    pub fn rpl_flags(&self) -> [u8; 16] {
+        self.rpl
+    }
+
+    pub fn set_rpl_flags(&mut self, flags: [u8; 16]) {
+        self.rpl = flags;
+    }
+
+    /// The keypad, for the front-end to press and release keys.
+    pub fn keyboard(&self) -> &Keyboard {
+        &self.keyboard
+    }
+
+    /// Presses `key` on the keypad, e.g. to script input in headless tests.
+    pub fn press_key(&self, key: u8) {
+        self.keyboard.set_key(key);
+    }
+
+    pub fn release_key(&self, key: u8) {
+        self.keyboard.release_key(key);
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    pub fn sound_timer(&self) -> &SoundTimer {
+        &self.sound_timer
+    }
+
+    pub fn sound_timer_mut(&mut self) -> &mut SoundTimer {
+        &mut self.sound_timer
+    }
+
+    /// Mutable access to the display, needed by `Renderer::render` to clear the dirty flag.
+    pub fn screen_mut(&mut self) -> &mut Screen {
+        &mut self.screen
+    }
+
+    /// Executes a single instruction unless the CPU is paused.
+    pub fn step(&mut self) -> Result<(), CpuError> {
+        if self.is_paused || self.is_halted || self.is_idle {
+            trace!("CPU is paused, halted or idle, skipping cycle.");
+            return Ok(());
+        };
+
+        if self.rewind.as_mut().is_some_and(|rewind| rewind.tick()) {
+            let state = self.snapshot();
+            if let Some(rewind) = self.rewind.as_mut() {
+                rewind.push(state);
+            };
+        };
+
+        if self.undo.is_some() {
+            let entry = self.undo_entry();
+            if let Some(undo) = self.undo.as_mut() {
+                undo.push(entry);
+            };
+        };
+
+        let program_counter = self.program_counter;
+        self.cycle().inspect_err(|&error| {
+            self.halt(CpuHalted {
+                reason: HaltReason::Error(error),
+                program_counter,
+            })
+        })
+    }
+
+    /// Steps up to `n` instructions like `run_cycles` and returns what each of them did, as a
+    /// compact execution trace for debugging. Stops early once the CPU pauses, halts or idles;
+    /// an instruction that fails is logged and halts the CPU as usual and is not part of the
+    /// trace.
+    pub fn step_n_traced(&mut self, n: usize) -> Vec<ExecutedInstruction> {
+        // `n` may well be usize::MAX to trace until the CPU stops.
+        let mut trace = Vec::with_capacity(n.min(1024));
+
+        for _ in 0..n {
+            if self.is_paused || self.is_halted || self.is_idle {
+                trace!("CPU is paused, halted or idle, stopping the trace.");
+                break;
+            };
+
+            let program_counter = self.program_counter;
+            let Ok(opcode) = self.current_opcode() else {
+                // Let `step` report the error.
+                let _ = self.step();
+                break;
+            };
+            let v = self.v.all();
+            let i = self.i.read();
+            let memory = self.memory_written_by(opcode);
+
+            if self.step().is_err() {
+                break;
+            };
+
+            let register_changes = Register::ALL
+                .into_iter()
+                .filter(|&register| v[register as usize] != self.v.get(register))
+                .map(|register| RegisterChange {
+                    register,
+                    old: v[register as usize],
+                    new: self.v.get(register),
+                    program_counter,
+                })
+                .collect();
+            let memory_changes = memory
+                .map(|(start, data)| {
+                    data.into_iter()
+                        .zip(start..)
+                        .filter_map(|(old, address)| {
+                            let new = self.ram.read(address).ok()?;
+
+                            (old != new).then_some(MemoryChange { address, old, new })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            trace.push(ExecutedInstruction {
+                program_counter,
+                opcode,
+                instruction: Instruction::decode(opcode).ok(),
+                register_changes,
+                i_change: (self.i.read() != i).then_some((i, self.i.read())),
+                memory_changes,
+            });
+        }
+
+        trace
+    }
+
+    /// Returns a receiver that gets a `CpuHalted` whenever an instruction fails or the program
+    /// exits.
+    pub fn on_halt(&mut self) -> Receiver<CpuHalted> {
+        let (sender, receiver) = channel();
+        self.halt_listeners.push(sender);
+
+        receiver
+    }
+
+    /// Logs the halt and notifies the `on_halt` receivers.
+    /// A failure pauses the CPU so it can be inspected and resumed, an exit stops it for good and
+    /// an idle loop stops executing until the state is restored or reset.
+    fn halt(&mut self, halted: CpuHalted) {
+        match halted.reason {
+            HaltReason::Exit => {
+                info!("Program exited at {:#05X}.", halted.program_counter);
+                self.is_halted = true;
+            }
+            HaltReason::Idle => {
+                info!("Program idles at {:#05X}.", halted.program_counter);
+                self.is_idle = true;
+            }
+            HaltReason::Error(error) => {
+                error!("CPU halted at {:#05X}: {:?}", halted.program_counter, error);
+                self.pause();
+            }
+        };
+
+        // Drop listeners whose receiver is gone.
+        self.halt_listeners
+            .retain(|listener| listener.send(halted).is_ok());
+    }
+
+    pub fn snapshot(&self) -> CpuState {
+        CpuState {
+            program_counter: self.program_counter,
+            ram: self.ram.clone(),
+            stack: self.stack.clone(),
+            sound_timer: self.sound_timer.read(),
+            delay_timer: self.delay_timer.read(),
+            v: self.v.clone(),
+            i: self.i.clone(),
+            rpl: self.rpl,
+            screen: self.screen.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, state: &CpuState) {
+        trace!("Restoring CPU state.");
+
+        self.is_idle = false;
+        self.program_counter = state.program_counter;
+        self.ram = state.ram.clone();
+        self.stack = state.stack.clone();
+        self.sound_timer.write(state.sound_timer);
+        self.delay_timer.write(state.delay_timer);
+        self.v = state.v.clone();
+        self.i = state.i.clone();
+        self.rpl = state.rpl;
+        self.screen = state.screen.clone();
+        self.screen.set_clip_sprites(self.quirks.clip_sprites);
+        self.screen.mark_dirty();
+    }
+
+    /// Starts capturing a snapshot every `interval` steps, keeping at most `capacity` of them.
+    /// Each snapshot holds a full copy of RAM and the screen, roughly 4.5 KB.
+    pub fn enable_rewind(&mut self, capacity: usize, interval: usize) {
+        info!(
+            "Enabling rewind with {} snapshots every {} steps.",
+            capacity, interval
+        );
+        self.rewind = Some(RewindBuffer::new(capacity, interval));
+    }
+
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    /// Restores the snapshot `steps` captures back, 1 being the most recent one.
+    /// Newer snapshots are dropped. Returns false and leaves the CPU untouched if there are
+    /// fewer than `steps` snapshots.
+    pub fn rewind(&mut self, steps: usize) -> bool {
+        let Some(state) = self.rewind.as_mut().and_then(|rewind| rewind.pop(steps)) else {
+            return false;
+        };
+
+        info!("Rewinding {} snapshots.", steps);
+        self.restore(&state);
+
+        true
+    }
+
+    /// Starts recording undo information so the last `depth` instructions can be stepped back.
+    pub fn enable_undo(&mut self, depth: usize) {
+        info!("Enabling undo with a depth of {} instructions.", depth);
+        self.undo = Some(UndoStack::new(depth));
+    }
+
+    pub fn disable_undo(&mut self) {
+        self.undo = None;
+    }
+
+    /// Reverts the last executed instruction. Returns false if there is nothing to undo.
+    /// Timers keep running and are not reverted.
+    pub fn step_back(&mut self) -> bool {
+        let Some(entry) = self.undo.as_mut().and_then(|undo| undo.pop()) else {
+            return false;
+        };
+
+        trace!("Stepping back to {}", entry.program_counter);
+
+        self.is_idle = false;
+        self.program_counter = entry.program_counter;
+        self.v = entry.v;
+        self.i = entry.i;
+        self.stack = entry.stack;
+        self.rpl = entry.rpl;
+
+        if let Some((address, data)) = entry.memory {
+            self.ram
+                .write_buf(address, &data)
+                .expect(&format!("Could not restore RAM({})!", address));
+        };
+
+        if let Some(screen) = entry.screen {
+            self.screen = screen;
+            self.screen.mark_dirty();
+        };
+
+        true
+    }
+
+    /// Records what the instruction at the program counter is about to change.
+    fn undo_entry(&self) -> UndoEntry {
+        let opcode = self.fetch_opcode(self.program_counter).unwrap_or(0);
+        let memory = self.memory_written_by(opcode);
+
+        // Clearing, drawing, switching the resolution and selecting planes change the screen.
+        let changes_screen = matches!(opcode, 0x00E0 | 0x00FE | 0x00FF)
+            || opcode & 0xF000 == 0xD000
+            || opcode & 0xF0FF == 0xF001;
+        let screen = changes_screen.then(|| self.screen.clone());
+
+        UndoEntry {
+            program_counter: self.program_counter,
+            v: self.v.clone(),
+            i: self.i.clone(),
+            stack: self.stack.clone(),
+            rpl: self.rpl,
+            memory,
+            screen,
+        }
+    }
+
+    /// Returns the start address and current contents of the RAM that `opcode` may write, or
+    /// None if it writes no RAM. Only Fx33, Fx55 and 5xy2 do.
+    fn memory_written_by(&self, opcode: u16) -> Option<(u16, Vec<u8>)> {
+        let x = (opcode & 0x0F00) >> 8;
+        let y = (opcode & 0x00F0) >> 4;
+        let i = self.i.read();
+
+        let written_length = match opcode & 0xF0FF {
+            0xF033 => Some(3),
+            0xF055 => Some(x + 1),
+            _ if opcode & 0xF00F == 0x5002 => Some(x.abs_diff(y) + 1),
+            _ => None,
+        };
+
+        written_length.map(|length| {
+            let end = (i as usize + length as usize).min(RAM_SIZE);
+            let data = (i as usize..end)
+                .map(|address| self.ram.read(address as u16).unwrap_or(0))
+                .collect();
+
+            (i, data)
+        })
+    }
+
+    fn cycle(&mut self) -> Result<(), CpuError> {
+        trace!("--- New Cycle ---");
+        trace!("Program Counter: {}", self.program_counter);
+
+        let program_counter = self.program_counter;
+        let opcode = self.fetch_opcode(program_counter)?;
+
+        trace!("OPCODE: {}", opcode);
+
+        if let Some(hook) = self.pre_execute_hook.as_mut() {
+            hook(opcode, program_counter);
+        };
+
+        // Only copy the registers if anyone is watching.
+        let registers_before = (self.watched_registers != 0).then(|| self.v.clone());
+
+        // The program counter is already past an invalid opcode, so stepping over it only means
+        // not failing.
+        match (self.execute_instruction(opcode), self.invalid_opcode_policy) {
+            (Err(CpuError::InvalidOpcode(_)), InvalidOpcodePolicy::Skip) => {
+                warn!(
+                    "Skipping invalid opcode {:#06X} at {:#05X}",
+                    opcode, program_counter
+                );
+                self.unsupported_opcodes.insert(opcode);
+                self.deviation(format!(
+                    "Stepped over invalid opcode {:#06X} at {:#05X}",
+                    opcode, program_counter
+                ));
+            }
+            (Err(CpuError::InvalidOpcode(_)), InvalidOpcodePolicy::Nop) => {
+                trace!("Invalid opcode {:#06X} treated as no-op", opcode);
+                self.unsupported_opcodes.insert(opcode);
+                self.deviation(format!(
+                    "Stepped over invalid opcode {:#06X} at {:#05X}",
+                    opcode, program_counter
+                ));
+            }
+            (result, _) => result?,
+        };
+
+        self.stats.instruction_count += 1;
+
+        if let Some(histogram) = self.opcode_histogram.as_mut() {
+            *histogram.entry(opcode).or_insert(0) += 1;
+        };
+
+        if let Some(registers_before) = registers_before {
+            self.record_register_changes(&registers_before, program_counter);
+        };
+
+        if let Some(hook) = self.post_execute_hook.as_mut() {
+            hook(opcode, program_counter);
+        };
+
+        trace!("End of Cycle");
+
+        Ok(())
+    }
+
+    /// Compares the watched registers against `before` and logs the ones that changed.
+    fn record_register_changes(&mut self, before: &V, program_counter: u16) {
+        for register in Register::ALL {
+            if self.watched_registers & (1 << register as u16) == 0 {
+                continue;
+            };
+
+            let (old, new) = (before.get(register), self.v.get(register));
+            if old != new {
+                trace!("{:?} changed from {} to {}", register, old, new);
+                self.register_changes.push(RegisterChange {
+                    register,
+                    old,
+                    new,
+                    program_counter,
+                });
+            };
+        }
+    }
+
+    /// Reads the instruction at `pc`. CHIP-8 stores opcodes big-endian, high byte first.
+    /// Odd addresses are valid, but both bytes have to lie inside RAM.
+    pub fn fetch_opcode(&self, pc: u16) -> Result<u16, CpuError> {
+        self.ram.read_u16(pc).map_err(|_| CpuError::OutOfBounds(pc))
+    }
+
+    /// The opcode at the program counter, i.e. the one the next `step` executes.
+    pub fn current_opcode(&self) -> Result<u16, CpuError> {
+        self.fetch_opcode(self.program_counter)
+    }
+
+    /// Decodes the opcode at the program counter without executing it, e.g. for a debugger to
+    /// show the next instruction.
+    pub fn current_instruction(&self) -> Result<Instruction, CpuError> {
+        Instruction::decode(self.current_opcode()?)
+    }
+
+    fn execute_instruction(&mut self, opcode: u16) -> Result<(), CpuError> {
+        // Increment the program counter by 2 because one instruction is 2 bytes long (u16).
+        self.increment_program_counter()?;
+
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+
+        // match instructions
+        match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00E0 => {
+                    trace!("Clearing screen.");
+                    self.screen.clear()
+                }
+                0x00FE => {
+                    trace!("Switch to low resolution.");
+                    self.screen.set_mode(ScreenMode::LoRes);
+                }
+                0x00FD => {
+                    trace!("Exit interpreter.");
+                    self.halt(CpuHalted {
+                        reason: HaltReason::Exit,
+                        program_counter: self.program_counter - 2,
+                    });
+                }
+                0x00FF => {
+                    trace!("Switch to high resolution.");
+                    self.screen.set_mode(ScreenMode::HiRes);
+                }
+                0x00EE => {
+                    self.program_counter = self.stack.pop()?;
+                    trace!(
+                        "Return from a subroutine. New program counter: {}",
+                        self.program_counter
+                    );
+                }
+                _ => {
+                    // Instruction 0nnn
+
+                    let nnn = opcode & 0xFFF;
+                    self.deviation(format!(
+                        "Machine code call {:#06X} treated as a jump",
+                        opcode
+                    ));
+
+                    trace!("Set ProgramCounter to {}", nnn);
+
+                    self.program_counter = nnn;
+                }
+            },
+            0x1000 => {
+                let address = self.program_counter - 2;
+                self.program_counter = opcode & 0xFFF;
+                trace!("Jump to {}", self.program_counter);
+
+                if self.program_counter == address {
+                    self.halt(CpuHalted {
+                        reason: HaltReason::Idle,
+                        program_counter: address,
+                    });
+                };
+            }
+            0x2000 => {
+                // The program counter already points at the instruction after the CALL,
+                // which is the return address.
+                let nnn = opcode & 0xFFF;
+
+                self.stack.push(self.program_counter).inspect_err(|_| {
+                    warn!(
+                        "Stack overflow calling {:#05X} with {} of {} return addresses in use",
+                        nnn,
+                        self.stack.depth(),
+                        self.stack.capacity()
+                    )
+                })?;
+                self.stats.max_stack_depth = self.stats.max_stack_depth.max(self.stack.depth());
+
+                if self.record_calls {
+                    let call_site = self.program_counter - 2;
+                    let callers = self.call_graph.entry(nnn).or_default();
+                    if !callers.contains(&call_site) {
+                        callers.push(call_site);
+                    };
+                };
+
+                self.program_counter = nnn;
+                trace!("Call subroutine at {}", nnn);
+            }
+            0x3000 => {
+                trace!("Skip next instruction if V({}) == KK.", x);
+                let vx = self.v.read(x).expect(&format!("Could not read V({})!", x));
+                let kk = (opcode & 0xFF) as u8;
+
+                if vx == kk {
+                    trace!("Skipping next instruction.");
+                    self.increment_program_counter()?;
+                };
+            }
+            0x4000 => {
+                trace!("Skip next instruction if V({}) != KK.", x);
+                let vx = self.v.read(x).expect(&format!("Could not read V({})!", x));
+                let kk = (opcode & 0xFF) as u8;
+
+                if vx != kk {
+                    trace!("Skipping next instruction.");
+                    self.increment_program_counter()?;
+                };
+            }
+            0x5000 => {
+                // Only 5xy0 is a CHIP-8 instruction, XO-CHIP adds 5xy2 and 5xy3.
+                match opcode & 0xF {
+                    0x0 => {
+                        trace!("Skip next instruction if V({}) == V({}).", x, y);
+                        let vx = self.v.read(x).expect(&format!("Could not read V({})!", x));
+                        let vy = self.v.read(y).expect(&format!("Could not read V({})!", x));
+
+                        if vx == vy {
+                            trace!("Skipping instruction.");
+                            self.increment_program_counter()?;
+                        };
+                    }
+                    0x2 if self.quirks.xo_chip_instructions => {
+                        let i = self.i.read();
+                        trace!(
+                            "Store V({}) through V({}) in memory starting at I{}",
+                            x,
+                            y,
+                            i
+                        );
+                        // Fail before writing anything if not all registers fit.
+                        if i as usize + x.abs_diff(y) as usize + 1 > RAM_SIZE {
+                            return Err(CpuError::OutOfBounds(i));
+                        };
+
+                        self.check_protection(i)?;
+
+                        let values: Vec<u8> = Self::register_range(x, y)
+                            .map(|register| {
+                                self.v
+                                    .read(register)
+                                    .expect(&format!("Could not read V({})!", register))
+                            })
+                            .collect();
+                        self.ram.write_buf(i, &values)?;
+                    }
+                    0x3 if self.quirks.xo_chip_instructions => {
+                        let i = self.i.read();
+                        trace!(
+                            "Load V({}) through V({}) from memory starting at I{}",
+                            x,
+                            y,
+                            i
+                        );
+
+                        let length = x.abs_diff(y) as u16 + 1;
+                        if i as usize + length as usize > RAM_SIZE {
+                            return Err(CpuError::OutOfBounds(i));
+                        };
+
+                        let values = self.ram.read_range(i, length)?.to_vec();
+                        for (register, value) in Self::register_range(x, y).zip(values) {
+                            self.v
+                                .write(register, value)
+                                .expect(&format!("Could not write V({})!", register));
+                        }
+                    }
+                    _ => return Err(CpuError::InvalidOpcode(opcode)),
+                }
+            }
+            0x6000 => {
+                let kk = (opcode & 0xFF) as u8;
+                trace!("Setting V({}) to {}", x, kk);
+                self.v
+                    .write(x, kk)
+                    .expect(&format!("Could not write {} to V({})", kk, x));
+            }
+            0x7000 => {
+                let vx = self.v.read(x).expect(&format!("Could not read V({})!", x));
+                let kk = (opcode & 0xFF) as u8;
+                trace!("Set V({}) to {} + {}", x, vx, kk);
+                self.v.write(x, vx.wrapping_add(kk)).expect(&format!(
+                    "Could not write {} to V({})!",
+                    vx as u16 + kk as u16,
+                    x
+                ));
+            }
+            0x8000 => match opcode & 0xF {
+                0x0 => {
+                    trace!("Set V({}) to V({})", x, y);
+                    self.v
+                        .write(
+                            x,
+                            self.v.read(y).expect(&format!("Could not read V({})!", y)),
+                        )
+                        .expect(&format!("Could not write V({}) to V({})!", y, x))
+                }
+                0x1 => {
+                    trace!("Set V({}) to V({}) | V({})", x, x, y);
+                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
+                    let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
+                    self.v.write(x, vx | vy).expect(&format!(
+                        "Could not write {} to V({})",
+                        vx | vy,
+                        x
+                    ));
+
+                    self.reset_vf_quirk();
+                }
+                0x2 => {
+                    trace!("Set V({}) to V({}) & V({})", x, x, y);
+                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
+                    let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
+                    self.v.write(x, vx & vy).expect(&format!(
+                        "Could not write {} to V({})",
+                        vx & vy,
+                        x
+                    ));
+
+                    self.reset_vf_quirk();
+                }
+                0x3 => {
+                    trace!("Set V({}) to V({}) ^ V({})", x, x, y);
+                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
+                    let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
+                    self.v.write(x, vx ^ vy).expect(&format!(
+                        "Could not write {} to V({})",
+                        vx ^ vy,
+                        x
+                    ));
+
+                    self.reset_vf_quirk();
+                }
+                0x4 => {
+                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
+                    let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
+
+                    let result = vx.wrapping_add(vy);
+
+                    let carry = if vx as u16 + vy as u16 > u8::MAX as u16 {
+                        1
+                    } else {
+                        0
+                    };
+
+                    trace!(
+                        "Set V({}) = V({}) + V({}), set V(0xF) = Carry {}",
+                        x,
+                        x,
+                        y,
+                        carry
+                    );
+
+                    self.v.write(x, result).expect(&format!(
+                        "Could not write sum of {} and {} to V({})!",
+                        vx, vy, x
+                    ));
+
+                    // Set carry last so it wins if x is 0xF.
+                    self.v
+                        .write(0xF, carry)
+                        .expect(&format!("Could not write carry to V({})!", 0xF));
+                }
+                0x5 => {
+                    // Vx -= Vy. VF is 1 if no borrow is needed, which is the case for Vx >= Vy.
+                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
+                    let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
+
+                    let not_borrow = if vx >= vy { 1 } else { 0 };
+
+                    trace!(
+                        "Set V({}) = V({}) {} - V({}) {}, set V(0xF) = NOT Borrow {}",
+                        x,
+                        x,
+                        vx,
+                        y,
+                        vy,
+                        not_borrow
+                    );
+
+                    let result = vx.wrapping_sub(vy);
+
+                    self.v.write(x, result).expect(&format!(
+                        "Could not write difference of {} and {} to V({})!",
+                        vx, vy, x
+                    ));
+
+                    // Set the flag last so it wins if x is 0xF.
+                    self.v
+                        .write(0xF, not_borrow)
+                        .expect(&format!("Could not write NOT borrow to V({})!", 0xF));
+                }
+                0x6 => {
+                    let source = if self.quirks.shift_uses_vy { y } else { x };
+                    let value = self
+                        .v
+                        .read(source)
+                        .expect(&format!("Could not read V({})", source));
+
+                    trace!("Set V({}) = V({}) SHR 1", x, source);
+
+                    self.v
+                        .write(x, value >> 1)
+                        .expect(&format!("Could not write to V({})", x));
+
+                    // Set the shifted out bit last so it wins if x is 0xF.
+                    self.v.write(0xF, value & 0x1).expect(&format!(
+                        "Could not write {} to V({})",
+                        value & 0x1,
+                        0xF
+                    ));
+                }
+                0x7 => {
+                    // Vx = Vy - Vx. VF is 1 if no borrow is needed, which is the case for Vy >= Vx.
+                    let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
+                    let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
+
+                    let not_borrow = if vy >= vx { 1 } else { 0 };
+
+                    trace!(
+                        "Set V({}) = V({}) - V({}), set V(0xF) = NOT Borrow {}",
+                        x,
+                        y,
+                        x,
+                        not_borrow
+                    );
+
+                    let result = vy.wrapping_sub(vx);
+                    self.v
+                        .write(x, result)
+                        .expect(&format!("Could not write {} to V({})!", result, x));
+
+                    // Set the flag last so it wins if x is 0xF.
+                    self.v
+                        .write(0xF, not_borrow)
+                        .expect(&format!("Could not write {} to V({})!", not_borrow, 0xF));
+                }
+                0xE => {
+                    let source = if self.quirks.shift_uses_vy { y } else { x };
+                    let value = self
+                        .v
+                        .read(source)
+                        .expect(&format!("Could not read V({})!", source));
+
+                    trace!("Set V({}) = V({}) SHL 1", x, source);
+
+                    self.v.write(x, value << 1).expect(&format!(
+                        "Could not write {} to V({})!",
+                        value << 1,
+                        x
+                    ));
+
+                    // Set the shifted out bit last so it wins if x is 0xF.
+                    self.v.write(0xF, value >> 7).expect(&format!(
+                        "Could not write {} to V({})!",
+                        value >> 7,
+                        0xF
+                    ));
+                }
+                _ => return Err(CpuError::InvalidOpcode(opcode)),
+            },
+            0x9000 => {
+                if opcode & 0xF != 0 {
+                    return Err(CpuError::InvalidOpcode(opcode));
+                };
+
+                let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
+                let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
+
+                trace!("Skip next instruction if V({}) != V({})", x, y);
+
+                if vx != vy {
+                    trace!("Skipping next instruction");
+                    self.increment_program_counter()?;
+                };
+            }
+            0xA000 => {
+                let nnn = opcode & 0xFFF;
+                trace!("Set I = {}", nnn);
+                self.i.write(nnn);
+            }
+            0xB000 => {
+                let nnn = opcode & 0xFFF;
+                let register = if self.quirks.jump_uses_vx { x } else { 0x0 };
+                let offset = self
+                    .v
+                    .read(register)
+                    .expect(&format!("Could not read V({})!", register));
+                self.program_counter = nnn + offset as u16;
+
+                trace!(
+                    "Jump to location {} + V({}) {} = {}",
+                    nnn,
+                    register,
+                    offset,
+                    self.program_counter
+                );
+            }
+            0xC000 => {
+                let kk = (opcode & 0xFF) as u8;
+                let rand_num: u8 = self.rng_override.unwrap_or_else(|| self.rng.gen::<u8>());
+
+                trace!("Set V({}) = RAND BYTE {} & {}", x, rand_num, kk);
+
+                self.v.write(x, rand_num & kk).expect(&format!(
+                    "Could not write {} to V({})!",
+                    rand_num & kk,
+                    x
+                ));
+            }
+            0xD000 => {
+                let n = opcode & 0xF;
+
+                if n == 0 {
+                    if self.quirks.zero_height_sprite_invalid {
+                        return Err(CpuError::InvalidOpcode(opcode));
+                    };
+
+                    // Leaves VF and the frame alone, as if the instruction was not there.
+                    self.deviation(format!("Zero-height sprite {:#06X} drew nothing", opcode));
+                    return Ok(());
+                };
+
+                let i = self.i.read();
+                let vx = self.v.read(x).expect(&format!("Could not read V({})!", x));
+                let vy = self.v.read(y).expect(&format!("Could not read V({})!", y));
+
+                // Every selected plane takes its own n rows.
+                let planes = self.screen.selected_plane_count() as u8;
+                let sprite = self.read_sprite_rows(n as u8 * planes.max(1))?.to_vec();
+                let collision = self.screen.draw_sprite(vx, vy, &sprite);
+
+                self.stats.draw_count += 1;
+                self.stats.collision_count += collision as u64;
+
+                trace!("Display {}-byte sprite starting at memory location I{} at (V({}), V({})), set V(0xF) = Collision {}", n, i, x, y, collision);
+
+                self.v
+                    .write(0xF, collision as u8)
+                    .expect(&format!("Could not write collision to V({})!", 0xF));
+
+                self.waiting_for_vblank = self.quirks.display_wait;
+            }
+            0xE000 => match opcode & 0xFF {
+                0x9E => {
+                    trace!(
+                        "Skip next instruction if key with the value of V({}) is pressed",
+                        x
+                    );
+                    if self
+                        .is_key_pressed(self.v.read(x).expect(&format!("Could not read V({})!", x)))
+                    {
+                        trace!("Skipping next instruction");
+                        self.increment_program_counter()?;
+                    };
+                }
+                0xA1 => {
+                    trace!(
+                        "Skip next instruction if key with the value of V({}) is not pressed",
+                        x
+                    );
+                    if !self
+                        .is_key_pressed(self.v.read(x).expect(&format!("Could not read V({})!", x)))
+                    {
+                        trace!("Skipping next instruction");
+                        self.increment_program_counter()?;
+                    };
+                }
+                _ => return Err(CpuError::InvalidOpcode(opcode)),
+            },
+            0xF000 => {
+                match opcode & 0xFF {
+                    0x01 if self.quirks.xo_chip_instructions => {
+                        trace!("Select planes {:02b}", x);
+                        self.screen.select_planes(x)?;
+                    }
+                    0x02 if x == 0 && self.quirks.xo_chip_instructions => {
+                        let i = self.i.read();
+                        trace!("Load audio pattern from I{}", i);
+
+                        let mut buffer = [0u8; PATTERN_SIZE];
+                        buffer.copy_from_slice(self.ram.read_range(i, PATTERN_SIZE as u16)?);
+                        self.sound_timer.load_pattern(buffer);
+                    }
+                    0x07 => {
+                        let delaytimer_value = self.delay_timer.read();
+                        trace!("Write delaytimer {} into V({})", delaytimer_value, x);
+
+                        self.v.write(x, delaytimer_value).expect(&format!(
+                            "Could not write delaytimer {} into v({})!",
+                            delaytimer_value, x
+                        ));
+                    }
+                    0x0F => {
+                        let delay_timer = self.delay_timer.read();
+                        trace!("Set V({}) = Delay Timer {}", x, delay_timer);
+                        self.v.write(x, self.delay_timer.read()).expect(&format!(
+                            "Could not write {} to V({})!",
+                            self.delay_timer.read(),
+                            x
+                        ))
+                    }
+                    0x0A => {
+                        self.is_paused = true;
+
+                        trace!("Wait for a key press");
+
+                        // A key held when the frame started counts right away.
+                        let latched_key = self
+                            .latched_keys
+                            .and_then(|keys| (0..=0xF).find(|key| keys & (1 << key) != 0));
+                        let key = match latched_key {
+                            Some(key) => key,
+                            None if self.blocking_key_wait => self.keyboard.wait_for_key(),
+                            None => match self.keyboard.pressed_keys().next() {
+                                Some(key) => key,
+                                None => {
+                                    trace!("No key pressed, retrying Fx0A on the next step");
+                                    self.program_counter -= 2;
+                                    self.is_paused = false;
+                                    return Ok(());
+                                }
+                            },
+                        };
+                        self.v
+                            .write(x, key)
+                            .expect(&format!("Could not write {} to V({})!", key, x));
+
+                        trace!(
+                            "Key {} pressed, stored the value of the key in V({})",
+                            key,
+                            x
+                        );
+
+                        self.is_paused = false;
+                    }
+                    0x15 => {
+                        trace!("Set delay timer = V({})", x);
+                        self.delay_timer
+                            .write(self.v.read(x).expect(&format!("Could not read V({})!", x)));
+                    }
+                    0x18 => {
+                        trace!("Set sound timer = V({})", x);
+                        self.sound_timer
+                            .write(self.v.read(x).expect(&format!("Could not read V({})!", x)));
+                    }
+                    0x3A if self.quirks.xo_chip_instructions => {
+                        let pitch = self.v.read(x).expect(&format!("Could not read V({})!", x));
+                        trace!("Set audio pitch = V({}) {}", x, pitch);
+                        self.sound_timer.set_pitch(pitch);
+                    }
+                    0x1E => {
+                        trace!("Set I = I{} + V({})", self.i.read(), x);
+                        let i = self.i.read().wrapping_add(
+                            self.v.read(x).expect(&format!("Could not read V({})!", x)) as u16,
+                        );
+                        self.i.write(i);
+
+                        if i > 0x0FFF {
+                            self.deviation(format!("I left the address space with {:#06X}", i));
+                        };
+
+                        if self.quirks.fx1e_overflow_flag {
+                            trace!("Set V(0xF) = I overflow {}", i > 0x0FFF);
+                            self.v
+                                .write(0xF, (i > 0x0FFF) as u8)
+                                .expect(&format!("Could not write overflow to V({})!", 0xF));
+                        };
+                    }
+                    0x29 => {
+                        // Only the low nibble selects the digit, so I always points into the font.
+                        let digit =
+                            self.v.read(x).expect(&format!("Could not read V({})!", x)) & 0xF;
+                        trace!("Set I = location of sprite for digit V({}) {}", x, digit);
+                        self.i.write(self.font_base + digit as u16 * 5);
+                    }
+                    0x30 => {
+                        let mut digit =
+                            self.v.read(x).expect(&format!("Could not read V({})!", x)) & 0xF;
+
+                        if digit > 9 {
+                            warn!(
+                                "There is no hi-res sprite for digit {}, using {}",
+                                digit,
+                                digit % 10
+                            );
+                            digit %= 10;
+                        };
+
+                        trace!(
+                            "Set I = location of hi-res sprite for digit V({}) {}",
+                            x,
+                            digit
+                        );
+                        self.i
+                            .write(Self::hires_font_base(self.font_base) + digit as u16 * 10);
+                    }
+                    0x33 => {
+                        let i = self.i.read();
+                        trace!("Store BCD representation of V({}) in memory locations I{}, I{}+1, and I{}+2", x, i, i, i);
+
+                        let vx = self.v.read(x).expect(&format!("Could not read V({})!", x));
+                        // Hundreds, tens and ones digit.
+                        let bcd = [vx / 100, vx / 10 % 10, vx % 10];
+
+                        // Fail before writing anything if not all three digits fit.
+                        if i as usize + bcd.len() > RAM_SIZE {
+                            return Err(CpuError::OutOfBounds(i));
+                        };
+
+                        self.check_protection(i)?;
+                        self.ram.write_buf(i, &bcd)?;
+                    }
+                    0x55 => {
+                        let i = self.i.read();
+                        trace!(
+                            "Store registers V(0) through V({}) in memory starting at location I{}",
+                            x,
+                            i
+                        );
+                        // Fail before writing anything if not all registers fit.
+                        if i as usize + x as usize + 1 > RAM_SIZE {
+                            return Err(CpuError::OutOfBounds(i));
+                        };
+
+                        self.check_protection(i)?;
+                        self.ram.write_buf(
+                            i,
+                            self.v
+                                .read_range(0, x + 1)
+                                .expect(&format!("Could not read range V(0, {})!", x)),
+                        )?;
+
+                        self.increment_i_quirk(x);
+                    }
+                    0x65 => {
+                        let i = self.i.read();
+                        trace!("Read registers V(0) through V({}) from memory starting at location I{}", x, i);
+                        if i as usize + x as usize + 1 > RAM_SIZE {
+                            return Err(CpuError::OutOfBounds(i));
+                        };
+
+                        self.v
+                            .write_buf(0, self.ram.read_range(i, x as u16 + 1)?)
+                            .expect(&format!("Could not write RAM({}, {}) to V(0)!", i, x));
+
+                        self.increment_i_quirk(x);
+                    }
+                    0x75 => {
+                        trace!("Store registers V(0) through V({}) in RPL user flags", x);
+                        for register in 0..=x {
+                            self.rpl[register as usize] = self
+                                .v
+                                .read(register)
+                                .expect(&format!("Could not read V({})!", register));
+                        }
+                    }
+                    0x85 => {
+                        trace!("Read registers V(0) through V({}) from RPL user flags", x);
+                        for register in 0..=x {
+                            self.v
+                                .write(register, self.rpl[register as usize])
+                                .expect(&format!("Could not write V({})!", register));
+                        }
+                    }
+                    _ => return Err(CpuError::InvalidOpcode(opcode)),
+                }
+            }
+            _ => return Err(CpuError::InvalidOpcode(opcode)),
+        };
+
+        Ok(())
+    }
+
+    pub fn clock_speed(&self) -> f64 {
+        self.clock_speed
+    }
+
+    /// Sets the number of instructions executed per second.
+    pub fn set_clock_speed(&mut self, clock_speed: f64) {
+        info!("Setting clock speed to {} Hz.", clock_speed);
+        self.clock_speed = clock_speed.max(0.);
+    }
+
+    pub fn is_turbo(&self) -> bool {
+        self.turbo
+    }
+
+    /// Turns fast-forward on or off, meant to follow a held key in the front-end.
+    /// Only the instructions per frame grow, frames and with them the timers stay at 60 Hz.
+    pub fn set_turbo(&mut self, turbo: bool) {
+        trace!("Setting turbo to {}", turbo);
+        self.turbo = turbo;
+    }
+
+    pub fn turbo_factor(&self) -> f64 {
+        self.turbo_factor
+    }
+
+    /// Sets how many times faster the CPU runs during turbo. Factors below 1 are treated as 1.
+    pub fn set_turbo_factor(&mut self, turbo_factor: f64) {
+        info!("Setting turbo factor to {}.", turbo_factor);
+        self.turbo_factor = turbo_factor.max(1.);
+    }
+
+    pub fn max_instructions_per_frame(&self) -> usize {
+        self.max_instructions_per_frame
+    }
+
+    /// Caps the instructions `run_frame` executes, so an absurd clock speed or turbo factor can
+    /// not stall the front-end for longer than a frame. Instructions over the cap are dropped
+    /// rather than owed to the next frame. A cap of 0 is treated as 1.
+    pub fn set_max_instructions_per_frame(&mut self, max_instructions_per_frame: usize) {
+        info!(
+            "Setting the maximum instructions per frame to {}.",
+            max_instructions_per_frame
+        );
+        self.max_instructions_per_frame = max_instructions_per_frame.max(1);
+    }
+
+    pub fn blocking_key_wait(&self) -> bool {
+        self.blocking_key_wait
+    }
+
+    /// By default Fx0A blocks the calling thread until a key is pressed. With blocking turned
+    /// off it returns right away if no key is held and leaves the program counter on the Fx0A,
+    /// so the next step waits again. Needed when the thread stepping the CPU is also the one
+    /// that has to deliver the key press, like the remote server.
+    pub fn set_blocking_key_wait(&mut self, blocking_key_wait: bool) {
+        info!("Setting blocking key wait to {}.", blocking_key_wait);
+        self.blocking_key_wait = blocking_key_wait;
+    }
+
+    /// Executes one 60 Hz frame worth of instructions, `clock_speed / 60` on average, and then
+    /// ticks both timers once. Fractions of an instruction are carried over to the next frame.
+    /// With the `display_wait` quirk the frame ends right after the first Dxyn, so `clock_speed`
+    /// becomes an upper bound and drawing ROMs run at no more than 60 sprites per second.
+    /// Returns the number of executed instructions.
+    pub fn run_frame(&mut self) -> Result<usize, CpuError> {
+        if self.is_paused {
+            trace!("CPU is paused, skipping frame.");
+            return Ok(0);
+        };
+
+        self.frame_cycle_remainder += if self.turbo {
+            self.clock_speed * self.turbo_factor
+        } else {
+            self.clock_speed
+        };
+        let cycles = (self.frame_cycle_remainder / FRAME_RATE).floor();
+        self.frame_cycle_remainder -= cycles * FRAME_RATE;
+
+        let cycles = if cycles > self.max_instructions_per_frame as f64 {
+            warn!(
+                "Frame scheduled {} instructions, capping at {}.",
+                cycles, self.max_instructions_per_frame
+            );
+            self.max_instructions_per_frame
+        } else {
+            cycles as usize
+        };
+
+        trace!("--- New Frame with {} cycles ---", cycles);
+
+        // Every cycle of the frame sees the same input, like interpreters that read the keypad
+        // once per frame.
+        self.latched_keys = Some(self.keyboard.state());
+        let executed = self.run_frame_cycles(cycles);
+        self.latched_keys = None;
+
+        let executed = executed?;
+        self.tick_timers();
+
+        Ok(executed)
+    }
+
+    fn run_frame_cycles(&mut self, cycles: usize) -> Result<usize, CpuError> {
+        let mut executed = 0;
+        for _ in 0..cycles {
+            if self.is_halted || self.is_idle {
+                trace!(
+                    "CPU halted or idle, ending frame after {} cycles.",
+                    executed
+                );
+                break;
+            };
+
+            self.step()?;
+            executed += 1;
+
+            if self.waiting_for_vblank {
+                trace!(
+                    "Waiting for vertical blank, ending frame after {} cycles.",
+                    executed
+                );
+                self.waiting_for_vblank = false;
+                break;
+            };
+        }
+
+        Ok(executed)
+    }
+
+    /// Steps until `predicate` holds or `max_cycles` instructions ran, e.g. to run a test until
+    /// the program counter reaches an address. The predicate is checked before every step, so
+    /// nothing runs if it already holds. Like `run_cycles`, stops early once the CPU pauses,
+    /// halts or idles. Returns the number of executed instructions.
+    pub fn run_until<F: FnMut(&CPU) -> bool>(
+        &mut self,
+        max_cycles: usize,
+        mut predicate: F,
+    ) -> Result<usize, CpuError> {
+        trace!(
+            "Running up to {} cycles until the predicate holds.",
+            max_cycles
+        );
+
+        let mut executed = 0;
+        while executed < max_cycles && !predicate(self) {
+            if self.is_paused || self.is_halted || self.is_idle {
+                trace!("CPU is paused, halted or idle, stopping.");
+                break;
+            };
+
+            self.step()?;
+            executed += 1;
+        }
+
+        // There are no frames to wait for here.
+        self.waiting_for_vblank = false;
+
+        Ok(executed)
+    }
+
+    /// Whether `key` is pressed, as latched at the start of the frame while `run_frame` runs.
+    fn is_key_pressed(&self, key: u8) -> bool {
+        match self.latched_keys {
+            Some(keys) => key <= 0xF && keys & (1 << key) != 0,
+            None => self.keyboard.is_key_pressed(key),
+        }
+    }
+
+    /// Counts the delay and sound timer down by one. `run_frame` does this every frame, hosts
+    /// that drive the CPU with `step` call it at 60 Hz themselves.
+    /// The timers are frozen while the CPU is paused, so a game resumes with the time it had left.
+    pub fn tick_timers(&mut self) {
+        if self.is_paused {
+            trace!("CPU is paused, not ticking the timers.");
+            return;
+        };
+
+        self.delay_timer.tick();
+        self.sound_timer.tick();
+    }
+
+    /// Executes up to `n` instructions back to back without any frame timing, so tests and
+    /// headless runs advance deterministically. Stops early once the program exits, a paused CPU
+    /// executes nothing.
+    pub fn run_cycles(&mut self, n: usize) -> Result<(), CpuError> {
+        trace!("Running {} cycles.", n);
+
+        for _ in 0..n {
+            if self.is_paused || self.is_halted || self.is_idle {
+                trace!("CPU is paused, halted or idle, stopping.");
+                break;
+            };
+
+            self.step()?;
+        }
+
+        // There are no frames to wait for here.
+        self.waiting_for_vblank = false;
+
+        Ok(())
+    }
+
+    /// Runs the CPU frame by frame until an instruction fails or the program exits.
+    /// Frames are scheduled against absolute deadlines, so time lost in one frame is made up
+    /// at the next frame boundary instead of drifting.
+    pub fn clock(&mut self) -> Result<(), CpuError> {
+        self.clock_until(None).map(|_| ())
+    }
+
+    /// Like `clock`, but returns once `duration` has passed.
+    /// Returns how often the loop woke up, at most one per frame even while paused.
+    pub fn clock_for(&mut self, duration: Duration) -> Result<usize, CpuError> {
+        self.clock_until(Some(Instant::now() + duration))
+    }
+
+    fn clock_until(&mut self, end: Option<Instant>) -> Result<usize, CpuError> {
+        let frame_duration = Duration::from_secs_f64(1. / FRAME_RATE);
+        let mut next_frame = Instant::now();
+        let mut wake_ups = 0;
+
+        while !self.is_halted && end.is_none_or(|end| Instant::now() < end) {
+            wake_ups += 1;
+
+            if self.is_paused {
+                // Sleep a whole frame, nothing runs that would have to be made up after resuming.
+                thread::sleep(frame_duration);
+                next_frame = Instant::now();
+                continue;
+            };
+
+            self.run_frame()?;
+
+            next_frame += frame_duration;
+            match next_frame.checked_duration_since(Instant::now()) {
+                Some(waiting_duration) => {
+                    trace!("Waiting {} ns", waiting_duration.as_nanos());
+                    thread::sleep(waiting_duration);
+                }
+                None => {
+                    // Catching up, but let the rest of the system run between frames.
+                    trace!("Frame overran, yielding.");
+                    thread::yield_now();
+                }
+            };
+        }
+
+        Ok(wake_ups)
+    }
+
+    /// Reads `n` sprite rows starting at I. Fails instead of wrapping if they run past the end
+    /// of RAM.
+    pub fn read_sprite_rows(&self, n: u8) -> Result<&[u8], CpuError> {
+        let i = self.i.read();
+
+        if i as usize + n as usize > RAM_SIZE {
+            return Err(CpuError::OutOfBounds(i));
+        };
+
+        Ok(self.ram.read_range(i, n as u16)?)
+    }
+
+    /// Fails if a program write starting at `address` would touch the interpreter area while
+    /// it is protected.
+    fn check_protection(&mut self, address: u16) -> Result<(), CpuError> {
+        if address >= PROGRAM_START {
+            return Ok(());
+        };
+
+        if self.interpreter_protection {
+            warn!("Blocked write to protected RAM({})", address);
+            return Err(MemoryError::Protected.into());
+        };
+
+        self.deviation(format!("Write to the interpreter area at {:#05X}", address));
+
+        Ok(())
+    }
+
+    /// Records lenient behavior the program relied on, see `set_strict`.
+    fn deviation(&mut self, message: String) {
+        if !self.strict {
+            return;
+        };
+
+        warn!("Strict mode: {}", message);
+        self.deviations.push(message);
+    }
+
+    /// Registers from V(x) to V(y) in the order 5xy2 and 5xy3 walk them, descending if x > y.
+    fn register_range(x: u8, y: u8) -> Box<dyn Iterator<Item = u8>> {
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
+        }
+    }
+
+    /// Advances I past the registers Fx55 and Fx65 just stored or loaded, if the quirk asks for it.
+    fn increment_i_quirk(&mut self, x: u8) {
+        if self.quirks.increment_i_on_load_store {
+            let i = self.i.read().wrapping_add(x as u16 + 1);
+            trace!("Increment I to {}", i);
+            self.i.write(i);
+        };
+    }
+
+    /// Clears VF after the logical 8xy1, 8xy2 and 8xy3 instructions if the quirk is enabled.
+    fn reset_vf_quirk(&mut self) {
+        if self.quirks.vf_reset {
+            trace!("Reset V(0xF)");
+            self.v
+                .write(0xF, 0)
+                .expect(&format!("Could not write 0 to V({})!", 0xF));
+        };
+    }
+
+    /// Moves the program counter to the next instruction.
+    /// Fails if that leaves RAM, which means the ROM ran off its end.
+    fn increment_program_counter(&mut self) -> Result<(), CpuError> {
+        self.program_counter = self.program_counter.wrapping_add(2);
+        trace!("Incremented Program Counter.");
+
+        if self.program_counter as usize >= RAM_SIZE {
+            return Err(CpuError::ProgramCounterOutOfBounds(self.program_counter));
+        };
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod cpu_tests {
+    use super::*;
+    use crate::builder::CpuBuilder;
+
+    #[test]
+    fn test_pause_resume() {
+        let mut cpu = CPU::new();
+        assert!(!cpu.is_paused());
+
+        cpu.pause();
+        assert!(cpu.is_paused());
+
+        cpu.resume();
+        assert!(!cpu.is_paused());
+
+        cpu.toggle_pause();
+        assert!(cpu.is_paused());
+        cpu.toggle_pause();
+        assert!(!cpu.is_paused());
+    }
+
+    #[test]
+    fn test_step_while_paused() {
+        let mut cpu = CPU::new();
+        // 6A42: Set V(A) = 0x42
+        cpu.load_rom(&[0x6A, 0x42]).unwrap();
+
+        cpu.pause();
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.program_counter, PROGRAM_START);
+        assert_eq!(cpu.v.read(0xA).unwrap(), 0);
+
+        cpu.resume();
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.program_counter, PROGRAM_START + 2);
+        assert_eq!(cpu.v.read(0xA).unwrap(), 0x42);
+    }
+
+    #[test]
+    fn test_load_rom_from_reader() {
+        let mut cpu = CPU::new();
+        cpu.load_rom_from_reader(std::io::Cursor::new([0x12, 0x34, 0x56]))
+            .unwrap();
+
+        assert_eq!(
+            cpu.ram.read_range(PROGRAM_START, 3).unwrap(),
+            &[0x12, 0x34, 0x56]
+        );
+
+        let too_large = vec![0u8; MAX_ROM_SIZE + 1];
+        assert!(matches!(
+            cpu.load_rom_from_reader(std::io::Cursor::new(too_large)),
+            Err(RomError::TooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_load_gzip_rom() {
+        use std::io::Write as _;
+
+        let data = [0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C];
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut raw = CPU::new();
+        raw.load_rom_from_reader(std::io::Cursor::new(data))
+            .unwrap();
+        let mut gzip = CPU::new();
+        gzip.load_rom_from_reader(std::io::Cursor::new(compressed))
+            .unwrap();
+
+        assert_eq!(
+            gzip.ram.read_range(0, RAM_SIZE as u16).unwrap(),
+            raw.ram.read_range(0, RAM_SIZE as u16).unwrap()
+        );
+        assert_eq!(
+            gzip.ram
+                .read_range(PROGRAM_START, data.len() as u16)
+                .unwrap(),
+            &data
+        );
+
+        // Only the magic number but no valid stream behind it.
+        assert!(matches!(
+            gzip.load_rom_from_reader(std::io::Cursor::new([0x1F, 0x8B, 0x00, 0x00])),
+            Err(RomError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_rom_from_path() {
+        let path = std::env::temp_dir().join(format!("chip_8_rom_{}.ch8", std::process::id()));
+        std::fs::write(&path, [0xA2, 0x2A]).unwrap();
+
+        let mut cpu = CPU::new();
+        let result = cpu.load_rom_from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        result.unwrap();
+        assert_eq!(cpu.ram.read_range(PROGRAM_START, 2).unwrap(), &[0xA2, 0x2A]);
+
+        assert!(matches!(
+            cpu.load_rom_from_path(std::env::temp_dir().join("chip_8_rom_does_not_exist.ch8")),
+            Err(RomError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_rom_for() {
+        for (platform, start) in [
+            (Platform::Chip8, 0x200),
+            (Platform::Eti660, 0x600),
+            (Platform::SuperChip, 0x200),
+        ] {
+            let mut cpu = CPU::new();
+            cpu.load_rom_for(platform, &[0x60, 0x2A]).unwrap();
+
+            assert_eq!(cpu.program_counter, start);
+            assert_eq!(cpu.ram.read_range(start, 2).unwrap(), &[0x60, 0x2A]);
+
+            cpu.step().unwrap();
+            assert_eq!(cpu.v.read(0).unwrap(), 0x2A);
+        }
+    }
+
+    #[test]
+    fn test_platform_reset_and_reload() {
+        let mut cpu = CpuBuilder::new()
+            .platform(Platform::Eti660)
+            .rom(&[0x60, 0x2A])
+            .build()
+            .unwrap();
+        cpu.step().unwrap();
+
+        cpu.reset();
+        assert_eq!(cpu.program_counter, 0x600);
+
+        // V1 = 7
+        cpu.reload_rom(&[0x61, 0x07]).unwrap();
+        assert_eq!(cpu.program_counter, 0x600);
+        assert_eq!(cpu.ram.read_range(0x600, 2).unwrap(), &[0x61, 0x07]);
+        assert_eq!(cpu.ram.read_range(PROGRAM_START, 2).unwrap(), &[0, 0]);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.v.read(1).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_reload_rom_from_path() {
+        let dir = std::env::temp_dir();
+        let valid = dir.join(format!("chip_8_reload_{}.ch8", std::process::id()));
+        let invalid = dir.join(format!("chip_8_reload_{}.txt", std::process::id()));
+        std::fs::write(&valid, [0x60, 0x2A]).unwrap();
+        std::fs::write(&invalid, [0x60]).unwrap();
+
+        // V1 = 7
+        let mut cpu = run(&[0x61, 0x07], 1);
+        let invalid_result = cpu.reload_rom_from_path(&invalid);
+        let missing_result = cpu.reload_rom_from_path(dir.join("chip_8_reload_missing.ch8"));
+
+        // Failed loads keep the running program.
+        assert_eq!(cpu.v.read(1).unwrap(), 7);
+        assert_eq!(cpu.program_counter, PROGRAM_START + 2);
+
+        let valid_result = cpu.reload_rom_from_path(&valid);
+        std::fs::remove_file(&valid).unwrap();
+        std::fs::remove_file(&invalid).unwrap();
+
+        assert!(matches!(
+            invalid_result,
+            Err(RomError::TooSmall { size: 1 })
+        ));
+        assert!(matches!(missing_result, Err(RomError::Io(_))));
+
+        valid_result.unwrap();
+        assert_eq!(cpu.v.read(1).unwrap(), 0);
+        assert_eq!(cpu.program_counter, PROGRAM_START);
+        cpu.step().unwrap();
+        assert_eq!(cpu.v.read(0).unwrap(), 0x2A);
+    }
+
+    #[test]
+    fn test_load_memory_image() {
+        let mut image = [0u8; RAM_SIZE];
+        for (address, byte) in image.iter_mut().enumerate() {
+            *byte = (address % 251) as u8;
+        }
+        // V0 = 0x2A at 0x200.
+        image[0x200..0x202].copy_from_slice(&[0x60, 0x2A]);
+
+        let mut cpu = CPU::new();
+        cpu.load_memory_image(&image);
+
+        // The font is gone.
+        assert_eq!(cpu.ram.read(DEFAULT_FONT_BASE).unwrap(), 0x50);
+        assert_eq!(cpu.ram.read(0).unwrap(), 0);
+        assert_eq!(cpu.ram.read(0xFFF).unwrap(), (0xFFF % 251) as u8);
+        assert_eq!(cpu.ram.read_range(0, RAM_SIZE as u16).unwrap(), &image);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.v.read(0).unwrap(), 0x2A);
+    }
+
+    #[test]
+    fn test_reset() {
+        // V0 = 0x42, I = 0x300, CALL 0x208, hi-res, LD [I], V0
+        let mut cpu = run(
+            &[
+                0x60, 0x42, 0xA3, 0x00, 0x22, 0x08, 0x00, 0x00, 0x00, 0xFF, 0xF0, 0x55,
+            ],
+            5,
+        );
+        cpu.set_quirks(Quirks::super_chip());
+        cpu.pause();
+
+        cpu.reset();
+
+        assert!(!cpu.is_paused());
+        assert_eq!(cpu.quirks(), Quirks::super_chip());
+        assert_eq!(cpu.program_counter, PROGRAM_START);
+        assert_eq!(cpu.v, V::new());
+        assert_eq!(cpu.i.read(), 0);
+        assert!(cpu.stack.frames().is_empty());
+        assert_eq!(cpu.screen, Screen::new());
+        assert_eq!(cpu.ram, CPU::boot_ram(DEFAULT_FONT_BASE));
+
+        cpu.load_rom(&[0x61, 0x01]).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.v.read(1).unwrap(), 0x01);
+    }
+
+    #[test]
+    fn test_reload_rom() {
+        // V0 = 0x42, I = 0x300, hi-res, CALL 0x208
+        let mut cpu = run(&[0x60, 0x42, 0xA3, 0x00, 0x00, 0xFF, 0x22, 0x08], 4);
+        assert_ne!(cpu.program_counter, PROGRAM_START);
+
+        cpu.reload_rom(&[0x61, 0x01]).unwrap();
+
+        assert_eq!(cpu.program_counter, PROGRAM_START);
+        assert_eq!(cpu.v, V::new());
+        assert_eq!(cpu.i.read(), 0);
+        assert_eq!(cpu.stack.depth(), 0);
+        assert_eq!(cpu.screen.mode(), ScreenMode::LoRes);
+        assert_eq!(
+            cpu.ram.read_range(PROGRAM_START, 4).unwrap(),
+            &[0x61, 0x01, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_fetch_at_end_of_ram() {
+        let mut cpu = CPU::new();
+
+        // The last full instruction starts at 0xFFE.
+        assert!(cpu.fetch_opcode((RAM_SIZE - 2) as u16).is_ok());
+        assert_eq!(
+            cpu.fetch_opcode((RAM_SIZE - 1) as u16),
+            Err(CpuError::OutOfBounds(0xFFF))
+        );
+
+        cpu.program_counter = (RAM_SIZE - 1) as u16;
+        assert!(matches!(cpu.step(), Err(CpuError::OutOfBounds(0xFFF))));
+
+        cpu.resume();
+        cpu.program_counter = RAM_SIZE as u16;
+        assert!(matches!(cpu.step(), Err(CpuError::OutOfBounds(0x1000))));
+
+        // Odd addresses inside RAM are fine.
+        assert!(cpu.fetch_opcode(PROGRAM_START + 1).is_ok());
+    }
+
+    #[test]
+    fn test_fetch_opcode_is_big_endian() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0xA2, 0xB4, 0x00, 0xE0]).unwrap();
+
+        assert_eq!(cpu.fetch_opcode(PROGRAM_START).unwrap(), 0xA2B4);
+        assert_eq!(cpu.fetch_opcode(PROGRAM_START + 1).unwrap(), 0xB400);
+        assert_eq!(cpu.fetch_opcode(PROGRAM_START + 2).unwrap(), 0x00E0);
+    }
+
+    #[test]
+    fn test_program_counter_leaves_ram() {
+        let mut cpu = CPU::new();
+
+        // CLS in the last instruction slot
+        cpu.ram
+            .write_buf((RAM_SIZE - 2) as u16, &[0x00, 0xE0])
+            .unwrap();
+        cpu.program_counter = (RAM_SIZE - 2) as u16;
+        assert_eq!(
+            cpu.step(),
+            Err(CpuError::ProgramCounterOutOfBounds(RAM_SIZE as u16))
+        );
+
+        // Skip the last instruction: SE V0, 0x00
+        cpu.resume();
+        cpu.ram
+            .write_buf((RAM_SIZE - 4) as u16, &[0x30, 0x00])
+            .unwrap();
+        cpu.program_counter = (RAM_SIZE - 4) as u16;
+        assert_eq!(
+            cpu.step(),
+            Err(CpuError::ProgramCounterOutOfBounds(RAM_SIZE as u16))
+        );
+    }
+
+    #[test]
+    fn test_read_sprite_rows() {
+        let mut cpu = CPU::new();
+
+        // The font for 0 starts at the font base.
+        cpu.i.write(DEFAULT_FONT_BASE);
+        assert_eq!(
+            cpu.read_sprite_rows(5).unwrap(),
+            &[0xF0, 0x90, 0x90, 0x90, 0xF0]
+        );
+
+        cpu.ram.write_buf(0xFFD, &[0x11, 0x22, 0x33]).unwrap();
+        cpu.i.write(0xFFD);
+        assert_eq!(cpu.read_sprite_rows(3).unwrap(), &[0x11, 0x22, 0x33]);
+        assert_eq!(cpu.read_sprite_rows(4), Err(CpuError::OutOfBounds(0xFFD)));
+
+        cpu.i.write(0xFFFF);
+        assert_eq!(cpu.read_sprite_rows(1), Err(CpuError::OutOfBounds(0xFFFF)));
+    }
+
+    #[test]
+    fn test_draw_past_end_of_ram() {
+        // I = 0xFFE, draw 3 rows
+        let mut cpu = run(&[0xAF, 0xFE, 0xD0, 0x03], 1);
+        assert_eq!(cpu.step(), Err(CpuError::OutOfBounds(0xFFE)));
+        assert!(cpu.screen.iter_pixels().all(|pixel| !pixel));
+    }
+
+    #[test]
+    fn test_collision_count() {
+        // I = sprite(0), draw at (0, 0) three times, draw at (8, 0)
+        let program = [
+            0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05, 0xD0, 0x05, 0x60, 0x08, 0xD0, 0x05,
+        ];
+        let mut cpu = run(&program, 2);
+        assert_eq!(
+            cpu.stats(),
+            CpuStats {
+                instruction_count: 2,
+                draw_count: 1,
+                collision_count: 0,
+                ..CpuStats::default()
+            }
+        );
+
+        // Erasing and redrawing, only the erase collides.
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(cpu.stats().collision_count, 1);
+
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(
+            cpu.stats(),
+            CpuStats {
+                instruction_count: 6,
+                draw_count: 4,
+                collision_count: 1,
+                ..CpuStats::default()
+            }
+        );
+
+        cpu.reset_stats();
+        assert_eq!(cpu.stats(), CpuStats::default());
+    }
+
+    #[test]
+    fn test_interpreter_protection() {
+        // V0 = 0xAB, I = 0x1FF, LD [I], V1
+        let program = [0x60, 0xAB, 0xA1, 0xFF, 0xF1, 0x55];
+
+        let mut cpu = run(&program, 2);
+        cpu.run_cycles(1).unwrap();
+        assert_eq!(cpu.ram.read(0x1FF).unwrap(), 0xAB);
+
+        let mut cpu = CPU::new();
+        cpu.set_interpreter_protection(true);
+        cpu.load_rom(&program).unwrap();
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(cpu.step(), Err(CpuError::Memory(MemoryError::Protected)));
+        assert_eq!(cpu.ram.read(0x1FF).unwrap(), 0);
+
+        // BCD V0 at I = 0x200 is still allowed.
+        cpu.resume();
+        cpu.i.write(0x200);
+        cpu.load_rom_at(0x300, &[0xF0, 0x33]).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.ram.read_range(0x200, 3).unwrap(), &[1, 7, 1]);
+
+        cpu.i.write(0x1FE);
+        cpu.load_rom_at(0x300, &[0xF0, 0x33]).unwrap();
+        assert_eq!(cpu.step(), Err(CpuError::Memory(MemoryError::Protected)));
+    }
+
+    #[test]
+    fn test_execute_hooks() {
+        use std::{cell::RefCell, rc::Rc};
+
+        let executed = Rc::new(RefCell::new(Vec::new()));
+        let finished = Rc::new(RefCell::new(0));
+
+        let mut cpu = CPU::new();
+        // V0 = 1, V1 = 2, invalid opcode
+        cpu.load_rom(&[0x60, 0x01, 0x61, 0x02, 0xE0, 0x00]).unwrap();
+
+        let executed_hook = executed.clone();
+        cpu.set_pre_execute_hook(Box::new(move |opcode, pc| {
+            executed_hook.borrow_mut().push((opcode, pc))
+        }));
+        let finished_hook = finished.clone();
+        cpu.set_post_execute_hook(Box::new(move |_, _| *finished_hook.borrow_mut() += 1));
+
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(
+            *executed.borrow(),
+            [(0x6001, PROGRAM_START), (0x6102, PROGRAM_START + 2)]
+        );
+        assert_eq!(*finished.borrow(), 2);
+
+        // A failing instruction only reaches the pre-execute hook.
+        assert!(cpu.step().is_err());
+        assert_eq!(executed.borrow().len(), 3);
+        assert_eq!(*finished.borrow(), 2);
+
+        cpu.clear_execute_hooks();
+        cpu.resume();
+        cpu.program_counter = PROGRAM_START;
+        cpu.step().unwrap();
+        assert_eq!(executed.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_bcd() {
+        // V0 = 254, I = 0x300, BCD V0
+        let cpu = run(&[0x60, 0xFE, 0xA3, 0x00, 0xF0, 0x33], 3);
+        assert_eq!(cpu.ram.read_range(0x300, 3).unwrap(), &[2, 5, 4]);
+
+        // V0 = 7
+        let cpu = run(&[0x60, 0x07, 0xA3, 0x00, 0xF0, 0x33], 3);
+        assert_eq!(cpu.ram.read_range(0x300, 3).unwrap(), &[0, 0, 7]);
+    }
+
+    #[test]
+    fn test_bcd_at_end_of_ram() {
+        // V0 = 254, I = 0xFFE, BCD V0
+        let mut cpu = run(&[0x60, 0xFE, 0xAF, 0xFE, 0xF0, 0x33], 2);
+
+        assert_eq!(cpu.step(), Err(CpuError::OutOfBounds(0xFFE)));
+        assert_eq!(cpu.ram.read(0xFFE).unwrap(), 0);
+        assert_eq!(cpu.ram.read(0xFFF).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_draw_sets_collision() {
+        let mut cpu = CPU::new();
+        // A050: I = sprite of digit 0, D015: draw it at (V0, V1), twice.
+        cpu.load_rom(&[0xA0, 0x50, 0xD0, 0x15, 0xD0, 0x15]).unwrap();
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.v.read(0xF).unwrap(), 0);
+        assert!(cpu.screen.iter_pixels().take(4).all(|pixel| pixel));
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.v.read(0xF).unwrap(), 1);
+        assert!(cpu.screen.iter_pixels().all(|pixel| !pixel));
+    }
+
+    /// Loads `program`, runs `steps` instructions and returns the CPU.
+    fn run(program: &[u8], steps: usize) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.load_rom(program).unwrap();
+
+        for _ in 0..steps {
+            cpu.step().unwrap();
+        }
+
+        cpu
+    }
+
+    #[test]
+    fn test_press_key_skips() {
+        // V0 = 5, skip if key V0 is pressed, V1 = 1, V2 = 2
+        let program = [0x60, 0x05, 0xE0, 0x9E, 0x61, 0x01, 0x62, 0x02];
+
+        let mut cpu = CPU::new();
+        cpu.load_rom(&program).unwrap();
+        cpu.press_key(5);
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.v.read(1).unwrap(), 0);
+        assert_eq!(cpu.v.read(2).unwrap(), 2);
+
+        cpu.reset();
+        cpu.load_rom(&program).unwrap();
+        cpu.release_key(5);
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.v.read(1).unwrap(), 1);
+        assert_eq!(cpu.v.read(2).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_keys_latched_per_frame() {
+        // V0 = 5, skip if key V0 is pressed, V1 = 1, skip if key V0 is pressed, V2 = 2
+        let program = [0x60, 0x05, 0xE0, 0x9E, 0x61, 0x01, 0xE0, 0x9E, 0x62, 0x02];
+
+        // A press in the middle of a frame is not seen until the next one.
+        let mut cpu = CPU::new();
+        cpu.load_rom(&program).unwrap();
+        cpu.latched_keys = Some(cpu.keyboard.state());
+        cpu.run_cycles(3).unwrap();
+        cpu.press_key(5);
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(cpu.v.read(1).unwrap(), 1);
+        assert_eq!(cpu.v.read(2).unwrap(), 2);
+
+        // Held from the start of the frame, both checks see it.
+        let mut cpu = CPU::new();
+        cpu.set_clock_speed(3. * FRAME_RATE);
+        cpu.load_rom(&program).unwrap();
+        cpu.press_key(5);
+        assert_eq!(cpu.run_frame().unwrap(), 3);
+        assert_eq!(cpu.program_counter, PROGRAM_START + 10);
+        assert_eq!(cpu.v.read(1).unwrap(), 0);
+        assert_eq!(cpu.v.read(2).unwrap(), 0);
+        assert_eq!(cpu.latched_keys, None);
+    }
+
+    #[test]
+    fn test_non_blocking_key_wait() {
+        // LD V3, K
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0xF3, 0x0A]).unwrap();
+        cpu.set_blocking_key_wait(false);
+
+        cpu.run_cycles(3).unwrap();
+        assert_eq!(cpu.program_counter, PROGRAM_START);
+        assert!(!cpu.is_paused());
+
+        cpu.press_key(7);
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, PROGRAM_START + 2);
+        assert_eq!(cpu.v.read(3).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_max_instructions_per_frame() {
+        // loop: V0 += 1, jump loop
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        assert_eq!(
+            cpu.max_instructions_per_frame(),
+            DEFAULT_MAX_INSTRUCTIONS_PER_FRAME
+        );
+
+        cpu.set_clock_speed(1e12);
+        assert_eq!(cpu.run_frame().unwrap(), DEFAULT_MAX_INSTRUCTIONS_PER_FRAME);
+
+        cpu.set_max_instructions_per_frame(6);
+        assert_eq!(cpu.run_frame().unwrap(), 6);
+        // The excess is not carried over.
+        cpu.set_clock_speed(60.);
+        assert_eq!(cpu.run_frame().unwrap(), 1);
+
+        cpu.set_max_instructions_per_frame(0);
+        assert_eq!(cpu.max_instructions_per_frame(), 1);
+    }
+
+    #[test]
+    fn test_run_until() {
+        // V0 = 0, loop: V0 += 3, jump loop
+        let program = [0x60, 0x00, 0x70, 0x03, 0x12, 0x02];
+
+        let mut cpu = CPU::new();
+        cpu.load_rom(&program).unwrap();
+        assert_eq!(
+            cpu.run_until(100, |cpu| cpu.program_counter() == 0x204),
+            Ok(2)
+        );
+        assert_eq!(cpu.program_counter(), 0x204);
+        // Already true, nothing runs.
+        assert_eq!(
+            cpu.run_until(100, |cpu| cpu.program_counter() == 0x204),
+            Ok(0)
+        );
+
+        let v0 = |cpu: &CPU| cpu.registers().get(Register::V0);
+        assert_eq!(cpu.run_until(100, |cpu| v0(cpu) >= 12), Ok(6));
+        assert_eq!(v0(&cpu), 12);
+
+        // Gives up after max_cycles.
+        assert_eq!(cpu.run_until(10, |cpu| v0(cpu) == 1), Ok(10));
+
+        // Stops when the program idles.
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x12, 0x00]).unwrap();
+        assert_eq!(cpu.run_until(100, |_| false), Ok(1));
+    }
+
+    #[test]
+    fn test_add_carry_into_vf() {
+        // VF = 0xFF, V1 = 0x02, VF += V1
+        let cpu = run(&[0x6F, 0xFF, 0x61, 0x02, 0x8F, 0x14], 3);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 1);
+
+        // VF = 0x01, V1 = 0x02, VF += V1
+        let cpu = run(&[0x6F, 0x01, 0x61, 0x02, 0x8F, 0x14], 3);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_sub_borrow_into_vf() {
+        // VF = 0x05, V1 = 0x02, VF -= V1
+        let cpu = run(&[0x6F, 0x05, 0x61, 0x02, 0x8F, 0x15], 3);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 1);
+
+        // VF = 0x01, V1 = 0x02, VF -= V1
+        let cpu = run(&[0x6F, 0x01, 0x61, 0x02, 0x8F, 0x15], 3);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_subn_borrow_into_vf() {
+        // VF = 0x02, V1 = 0x05, VF = V1 - VF
+        let cpu = run(&[0x6F, 0x02, 0x61, 0x05, 0x8F, 0x17], 3);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 1);
+
+        // VF = 0x05, V1 = 0x02, VF = V1 - VF
+        let cpu = run(&[0x6F, 0x05, 0x61, 0x02, 0x8F, 0x17], 3);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_sub_equal_operands() {
+        // V0 = 0x07, V1 = 0x07, V0 -= V1
+        let cpu = run(&[0x60, 0x07, 0x61, 0x07, 0x80, 0x15], 3);
+        assert_eq!(cpu.v.read(0x0).unwrap(), 0);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 1);
+
+        // V0 = 0x07, V1 = 0x07, V0 = V1 - V0
+        let cpu = run(&[0x60, 0x07, 0x61, 0x07, 0x80, 0x17], 3);
+        assert_eq!(cpu.v.read(0x0).unwrap(), 0);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_call_returns_after_call() {
+        let mut cpu = CPU::new();
+        // 0x200: CALL 0x206, 0x202: V0 = 0x01, 0x204: JP 0x204, 0x206: V1 = 0x02, 0x208: RET
+        cpu.load_rom(&[0x22, 0x06, 0x60, 0x01, 0x12, 0x04, 0x61, 0x02, 0x00, 0xEE])
+            .unwrap();
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x206);
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x202);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.v.read(0x0).unwrap(), 0x01);
+        assert_eq!(cpu.v.read(0x1).unwrap(), 0x02);
+    }
+
+    #[test]
+    fn test_stack_depth_stats() {
+        // 0x200: CALL 0x206, 0x202: JP 0x202, 0x204: RET, 0x206: CALL 0x204, 0x208: RET
+        let mut cpu = run(
+            &[0x22, 0x06, 0x12, 0x02, 0x00, 0xEE, 0x22, 0x04, 0x00, 0xEE],
+            2,
+        );
+        assert_eq!(cpu.stats().stack_depth, 2);
+        assert_eq!(cpu.stats().max_stack_depth, 2);
+
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(cpu.stats().stack_depth, 0);
+        assert_eq!(cpu.stats().max_stack_depth, 2);
+    }
+
+    #[test]
+    fn test_call_stack_overflow() {
+        let mut cpu = CPU::new();
+        // 0x200: CALL 0x200 recurses until the stack is full.
+        cpu.load_rom(&[0x22, 0x00]).unwrap();
+
+        for _ in 0..16 {
+            cpu.step().unwrap();
+        }
+
+        assert!(matches!(
+            cpu.step(),
+            Err(CpuError::Memory(MemoryError::StackOverflow))
+        ));
+    }
+
+    #[test]
+    fn test_return_stack_underflow() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x00, 0xEE]).unwrap();
+
+        assert!(matches!(
+            cpu.step(),
+            Err(CpuError::Memory(MemoryError::StackUnderflow))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut cpu = run(&[0x6A, 0x01, 0xA1, 0x23, 0x00, 0xE0], 2);
+        let state = cpu.snapshot();
+
+        cpu.step().unwrap();
+        cpu.v.write(0xA, 0xFF).unwrap();
+        cpu.screen.set_pixel(0, 0, true).unwrap();
+        assert_ne!(cpu.snapshot(), state);
+
+        cpu.restore(&state);
+        assert_eq!(cpu.snapshot(), state);
+        assert_eq!(cpu.program_counter, PROGRAM_START + 4);
+        assert_eq!(cpu.v.read(0xA).unwrap(), 0x01);
+        assert_eq!(cpu.i.read(), 0x123);
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut cpu = CPU::new();
+        // V0 = 0, then V0 += 1 forever.
+        cpu.load_rom(&[0x60, 0x00, 0x70, 0x01, 0x12, 0x02]).unwrap();
+        cpu.enable_rewind(4, 2);
+
+        assert!(!cpu.rewind(1));
+
+        let mut captured = Vec::new();
+        for step in 0..10 {
+            if step % 2 == 0 {
+                captured.push(cpu.snapshot());
+            };
+            cpu.step().unwrap();
+        }
+
+        // Only the last 4 captures are kept.
+        assert!(!cpu.rewind(5));
+
+        assert!(cpu.rewind(2));
+        assert_eq!(cpu.snapshot(), captured[captured.len() - 2]);
+
+        // The capture after the restored one is gone.
+        assert!(cpu.rewind(1));
+        assert_eq!(cpu.snapshot(), captured[captured.len() - 3]);
+        assert!(cpu.rewind(1));
+        assert!(!cpu.rewind(1));
+
+        // Snapshots are not reserved up front, so a huge capacity is fine.
+        cpu.enable_rewind(usize::MAX, 1);
+        cpu.step().unwrap();
+        assert!(cpu.rewind(1));
+    }
+
+    /// Like `run` but with `quirks` applied before the first step.
+    fn run_with_quirks(program: &[u8], steps: usize, quirks: Quirks) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.set_quirks(quirks);
+        cpu.load_rom(program).unwrap();
+
+        for _ in 0..steps {
+            cpu.step().unwrap();
+        }
+
+        cpu
+    }
+
+    #[test]
+    fn test_vf_reset_quirk() {
+        // VF = 1, V0 |= V1
+        let program = [0x6F, 0x01, 0x80, 0x11];
+
+        let cpu = run_with_quirks(&program, 2, Quirks::super_chip());
+        assert_eq!(cpu.v.read(0xF).unwrap(), 1);
+
+        let cpu = run_with_quirks(&program, 2, Quirks::cosmac_vip());
+        assert_eq!(cpu.v.read(0xF).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_shift_quirk() {
+        // V0 = 0x01, V1 = 0x81, V0 = V? SHR 1, V2 = 0x81, V2 = V? SHL 1
+        let program = [0x60, 0x01, 0x61, 0x81, 0x80, 0x16, 0x62, 0x81, 0x82, 0x1E];
+
+        let cpu = run_with_quirks(&program, 3, Quirks::super_chip());
+        assert_eq!(cpu.v.read(0x0).unwrap(), 0x00);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 1);
+
+        let cpu = run_with_quirks(&program, 3, Quirks::cosmac_vip());
+        assert_eq!(cpu.v.read(0x0).unwrap(), 0x40);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 1);
+
+        let cpu = run_with_quirks(&program, 5, Quirks::super_chip());
+        assert_eq!(cpu.v.read(0x2).unwrap(), 0x02);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 1);
+
+        let cpu = run_with_quirks(&program, 5, Quirks::cosmac_vip());
+        assert_eq!(cpu.v.read(0x2).unwrap(), 0x02);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_jump_quirk() {
+        // V0 = 0x10, V3 = 0x20, JP V0/V3 + 0x300
+        let program = [0x60, 0x10, 0x63, 0x20, 0xB3, 0x00];
+
+        let cpu = run_with_quirks(&program, 3, Quirks::cosmac_vip());
+        assert_eq!(cpu.program_counter, 0x310);
+
+        let cpu = run_with_quirks(&program, 3, Quirks::super_chip());
+        assert_eq!(cpu.program_counter, 0x320);
+    }
+
+    #[test]
+    fn test_store_load_registers_inclusive() {
+        // V0 = 0xA0, V1 = 0xA1, I = 0x300, LD [I], V0, I = 0x310, LD [I], VF
+        let mut cpu = run(
+            &[
+                0x60, 0xA0, 0x61, 0xA1, 0xA3, 0x00, 0xF0, 0x55, 0xA3, 0x10, 0xFF, 0x55,
+            ],
+            6,
+        );
+
+        // x = 0 stores exactly V0.
+        assert_eq!(cpu.ram.read_range(0x300, 2).unwrap(), &[0xA0, 0x00]);
+
+        // x = 0xF stores all 16 registers.
+        let mut expected = [0u8; 16];
+        expected[..2].copy_from_slice(&[0xA0, 0xA1]);
+        assert_eq!(cpu.ram.read_range(0x310, 16).unwrap(), &expected);
+        assert_eq!(cpu.ram.read(0x320).unwrap(), 0);
+
+        // I = 0x300, LD V0, [I] loads exactly V0, I = 0x311, LD VF, [I] loads all 16.
+        cpu.ram.write(0x301, 0xEE).unwrap();
+        cpu.ram.write_buf(0x311, &[0xB0; 16]).unwrap();
+        cpu.v.write_buf(0, &[0x55; 16]).unwrap();
+        cpu.load_rom_at(0x400, &[0xA3, 0x00, 0xF0, 0x65]).unwrap();
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(cpu.v.read(0).unwrap(), 0xA0);
+        assert_eq!(cpu.v.read(1).unwrap(), 0x55);
+
+        cpu.load_rom_at(0x400, &[0xA3, 0x11, 0xFF, 0x65]).unwrap();
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(cpu.v.all(), [0xB0; 16]);
+    }
+
+    #[test]
+    fn test_store_load_registers_at_end_of_ram() {
+        // V0 = 0xA0, V1 = 0xA1, I = 0xFFF, LD [I], V1
+        let mut cpu = run(&[0x60, 0xA0, 0x61, 0xA1, 0xAF, 0xFF, 0xF1, 0x55], 3);
+        assert_eq!(cpu.step(), Err(CpuError::OutOfBounds(0xFFF)));
+        assert_eq!(cpu.ram.read(0xFFF).unwrap(), 0);
+
+        // V0 = 0xA0, I = 0xFFF, LD V1, [I]
+        let mut cpu = run(&[0x60, 0xA0, 0xAF, 0xFF, 0xF1, 0x65], 2);
+        assert_eq!(cpu.step(), Err(CpuError::OutOfBounds(0xFFF)));
+        assert_eq!(cpu.v.read(0).unwrap(), 0xA0);
+
+        // A single register still fits at the last address: V0 = 0xA0, I = 0xFFF, LD [I], V0
+        let cpu = run(&[0x60, 0xA0, 0xAF, 0xFF, 0xF0, 0x55], 3);
+        assert_eq!(cpu.ram.read(0xFFF).unwrap(), 0xA0);
+    }
+
+    #[test]
+    fn test_load_store_increment_quirk() {
+        // I = 0x300, store V0..V2, I = 0x300, load V0..V2
+        let program = [0xA3, 0x00, 0xF2, 0x55, 0xA3, 0x00, 0xF2, 0x65];
+
+        let cpu = run_with_quirks(&program, 2, Quirks::super_chip());
+        assert_eq!(cpu.i.read(), 0x300);
+        let cpu = run_with_quirks(&program, 2, Quirks::cosmac_vip());
+        assert_eq!(cpu.i.read(), 0x303);
+
+        let cpu = run_with_quirks(&program, 4, Quirks::super_chip());
+        assert_eq!(cpu.i.read(), 0x300);
+        let cpu = run_with_quirks(&program, 4, Quirks::cosmac_vip());
+        assert_eq!(cpu.i.read(), 0x303);
+    }
+
+    #[test]
+    fn test_select_planes() {
+        // Select both planes, I = 0x300, draw 1 row per plane at (V0, V0)
+        let mut cpu = CPU::new();
+        cpu.set_quirks(Quirks::xo_chip());
+        cpu.ram.write_buf(0x300, &[0x80, 0xC0]).unwrap();
+        cpu.load_rom(&[0xF3, 0x01, 0xA3, 0x00, 0xD0, 0x01]).unwrap();
+        cpu.run_cycles(3).unwrap();
+
+        assert_eq!(cpu.screen.selected_planes(), 0b11);
+        assert_eq!(cpu.screen.get_plane_pixel(0, 0), Some(0b11));
+        assert_eq!(cpu.screen.get_plane_pixel(1, 0), Some(0b10));
+
+        // There is no plane 2.
+        cpu.load_rom(&[0xF4, 0x01]).unwrap();
+        assert_eq!(
+            cpu.step(),
+            Err(CpuError::Screen(ScreenError::InvalidPlanes(0b100)))
+        );
+        assert_eq!(cpu.screen.selected_planes(), 0b11);
+
+        // Not available without the XO-CHIP instructions.
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0xF1, 0x01]).unwrap();
+        assert_eq!(cpu.step(), Err(CpuError::InvalidOpcode(0xF101)));
+        assert_eq!(cpu.screen.selected_planes(), 0b01);
+    }
+
+    #[test]
+    fn test_clip_sprites_quirk() {
+        // V0 = 62, V1 = 31, I = font "0", draw 5 rows at (V0, V1)
+        let program = [0x60, 0x3E, 0x61, 0x1F, 0xA0, 0x50, 0xD0, 0x15];
+
+        let cpu = run_with_quirks(&program, 4, Quirks::default());
+        assert_eq!(cpu.screen.get_pixel(0, 31), Some(true));
+
+        let mut cpu = run_with_quirks(&program, 4, Quirks::super_chip());
+        assert_eq!(cpu.screen.iter_pixels().filter(|&pixel| pixel).count(), 2);
+
+        // Survives a reset.
+        cpu.reset();
+        assert!(cpu.screen.clip_sprites());
+    }
+
+    #[test]
+    fn test_draw_sprite_height() {
+        // VF = 5, I = 0x300, draw n rows at (V0, V0)
+        let draw = |n: u8| [0x6F, 0x05, 0xA3, 0x00, 0xD0, n];
+        let mut cpu = CPU::new();
+        cpu.ram.write_buf(0x300, &[0x80; 15]).unwrap();
+        cpu.load_rom(&draw(0x01)).unwrap();
+        cpu.run_cycles(3).unwrap();
+        assert_eq!(cpu.screen.iter_pixels().filter(|&pixel| pixel).count(), 1);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 0);
+
+        cpu.screen.clear();
+        cpu.load_rom(&draw(0x0F)).unwrap();
+        cpu.run_cycles(3).unwrap();
+        assert_eq!(cpu.screen.iter_pixels().filter(|&pixel| pixel).count(), 15);
+        assert_eq!(cpu.screen.get_pixel(0, 14), Some(true));
+
+        // 15 rows from I = 0xFF2 run past the end of RAM.
+        cpu.load_rom(&[0xAF, 0xF2, 0xD0, 0x0F]).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.step(), Err(CpuError::OutOfBounds(0xFF2)));
+
+        // n = 0 draws nothing and leaves VF alone.
+        cpu.reset();
+        cpu.set_strict(true);
+        cpu.load_rom(&draw(0x00)).unwrap();
+        cpu.run_cycles(3).unwrap();
+        assert!(cpu.screen.iter_pixels().all(|pixel| !pixel));
+        assert_eq!(cpu.v.read(0xF).unwrap(), 5);
+        assert_eq!(cpu.stats().draw_count, 0);
+        assert_eq!(cpu.deviations().len(), 1);
+
+        let mut cpu = CPU::new();
+        cpu.set_quirks(Quirks {
+            zero_height_sprite_invalid: true,
+            ..Quirks::default()
+        });
+        cpu.load_rom(&draw(0x00)).unwrap();
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(cpu.step(), Err(CpuError::InvalidOpcode(0xD000)));
+    }
+
+    #[test]
+    fn test_fx1e_overflow_quirk() {
+        // VF = 5, V0 = 0x01, I = 0xFFF, I += V0, I = 0xFFE, I += V0
+        let program = [
+            0x6F, 0x05, 0x60, 0x01, 0xAF, 0xFF, 0xF0, 0x1E, 0xAF, 0xFE, 0xF0, 0x1E,
+        ];
+        let overflow_flag = Quirks {
+            fx1e_overflow_flag: true,
+            ..Quirks::default()
+        };
+
+        let cpu = run_with_quirks(&program, 4, Quirks::default());
+        assert_eq!(cpu.i.read(), 0x1000);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 5);
+
+        let cpu = run_with_quirks(&program, 4, overflow_flag);
+        assert_eq!(cpu.i.read(), 0x1000);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 1);
+
+        let cpu = run_with_quirks(&program, 6, overflow_flag);
+        assert_eq!(cpu.i.read(), 0x0FFF);
+        assert_eq!(cpu.v.read(0xF).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_current_instruction() {
+        // V1 = 0x2A, I = 0x300, invalid
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x61, 0x2A, 0xA3, 0x00, 0xFF, 0xFF]).unwrap();
+
+        assert_eq!(cpu.current_opcode(), Ok(0x612A));
+        assert_eq!(
+            cpu.current_instruction(),
+            Ok(Instruction::LoadByte { x: 1, byte: 0x2A })
+        );
+        // Decoding does not execute.
+        assert_eq!(cpu.v.read(1).unwrap(), 0);
+
+        cpu.step().unwrap();
+        assert_eq!(
+            cpu.current_instruction(),
+            Ok(Instruction::LoadI { address: 0x300 })
+        );
+
+        cpu.step().unwrap();
+        assert_eq!(
+            cpu.current_instruction(),
+            Err(CpuError::InvalidOpcode(0xFFFF))
+        );
+
+        cpu.program_counter = RAM_SIZE as u16 - 1;
+        assert_eq!(
+            cpu.current_opcode(),
+            Err(CpuError::OutOfBounds(RAM_SIZE as u16 - 1))
+        );
+    }
+
+    #[test]
+    fn test_step_n_traced() {
+        // V0 = 0x12, I = 0x300, LD [I], V0, ADD V0, V0, invalid
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x60, 0x12, 0xA3, 0x00, 0xF0, 0x55, 0x80, 0x04, 0xFF, 0xFF])
+            .unwrap();
+
+        let trace = cpu.step_n_traced(10);
+        assert_eq!(trace.len(), 4);
+        assert!(cpu.is_paused());
+
+        assert_eq!(
+            trace[0],
+            ExecutedInstruction {
+                program_counter: 0x200,
+                opcode: 0x6012,
+                instruction: Some(Instruction::LoadByte { x: 0, byte: 0x12 }),
+                register_changes: vec![RegisterChange {
+                    register: Register::V0,
+                    old: 0,
+                    new: 0x12,
+                    program_counter: 0x200,
+                }],
+                i_change: None,
+                memory_changes: Vec::new(),
+            }
+        );
+        assert_eq!(trace[1].i_change, Some((0, 0x300)));
+        assert!(trace[1].register_changes.is_empty());
+        assert_eq!(
+            trace[2].memory_changes,
+            [MemoryChange {
+                address: 0x300,
+                old: 0,
+                new: 0x12,
+            }]
+        );
+        assert_eq!(trace[3].program_counter, 0x206);
+        assert_eq!(
+            trace[3]
+                .register_changes
+                .iter()
+                .map(|change| (change.register, change.new))
+                .collect::<Vec<_>>(),
+            [(Register::V0, 0x24)]
+        );
+
+        // Nothing runs while paused.
+        assert!(cpu.step_n_traced(1).is_empty());
+
+        // Tracing until the CPU stops: V0 = 1, V1 = 2, exit
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x60, 0x01, 0x61, 0x02, 0x00, 0xFD]).unwrap();
+        assert_eq!(cpu.step_n_traced(usize::MAX).len(), 3);
+    }
+
+    #[test]
+    fn test_set_program_counter() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x00, 0xE0, 0x61, 0x07, 0x62, 0x03]).unwrap();
+
+        cpu.set_program_counter(0x202).unwrap();
+        assert_eq!(cpu.program_counter(), 0x202);
+        cpu.step().unwrap();
+        assert_eq!(cpu.v.read(1).unwrap(), 7);
+
+        for program_counter in [RAM_SIZE as u16 - 1, RAM_SIZE as u16, u16::MAX] {
+            assert_eq!(
+                cpu.set_program_counter(program_counter),
+                Err(CpuError::ProgramCounterOutOfBounds(program_counter))
+            );
+        }
+        assert_eq!(cpu.program_counter(), 0x204);
+
+        // Odd addresses are valid, 0x203 reads the opcode 0x0762.
+        cpu.set_program_counter(RAM_SIZE as u16 - 2).unwrap();
+        cpu.set_program_counter(0x203).unwrap();
+        assert_eq!(cpu.current_opcode(), Ok(0x0762));
+    }
+
+    #[test]
+    fn test_font_location() {
+        // V0 = 0x07, I = sprite(V0)
+        let cpu = run(&[0x60, 0x07, 0xF0, 0x29], 2);
+        assert_eq!(cpu.i.read(), DEFAULT_FONT_BASE + 7 * 5);
+
+        // Only the low nibble counts.
+        let cpu = run(&[0x60, 0x2B, 0xF0, 0x29], 2);
+        assert_eq!(cpu.i.read(), DEFAULT_FONT_BASE + 0xB * 5);
+        assert_eq!(
+            cpu.ram.read_range(cpu.i.read(), 5).unwrap(),
+            &SPRITES[0xB * 5..0xB * 5 + 5]
+        );
+    }
+
+    #[test]
+    fn test_hires_font_location() {
+        // V0 = 0x03, I = hi-res sprite(V0)
+        let cpu = run(&[0x60, 0x03, 0xF0, 0x30], 2);
+        assert_eq!(cpu.i.read(), DEFAULT_FONT_BASE + 80 + 3 * 10);
+        assert_eq!(
+            cpu.ram.read_range(cpu.i.read(), 10).unwrap(),
+            &HIRES_SPRITES[30..40]
+        );
+
+        let cpu = run(&[0x60, 0x09, 0xF0, 0x30], 2);
+        assert_eq!(cpu.i.read(), DEFAULT_FONT_BASE + 80 + 9 * 10);
+    }
+
+    #[test]
+    fn test_set_font_base() {
+        let mut cpu = CPU::new();
+        cpu.set_font_base(0).unwrap();
+        assert_eq!(cpu.ram.read_range(0, 80).unwrap(), &SPRITES);
+        assert_eq!(cpu.ram.read_range(80, 100).unwrap(), &HIRES_SPRITES);
+
+        // The fonts are loaded at the new base again after a reset.
+        cpu.set_font_base(0x100).unwrap();
+        cpu.reset();
+        assert_eq!(cpu.ram.read_range(0x100, 80).unwrap(), &SPRITES);
+        assert!(cpu.ram.read_range(0, 80).unwrap().iter().all(|&b| b == 0));
+
+        // V0 = 0x0C, I = sprite(V0), V1 = 0x05, I = hi-res sprite(V1)
+        cpu.load_rom(&[0x60, 0x0C, 0xF0, 0x29, 0x61, 0x05, 0xF1, 0x30])
+            .unwrap();
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(cpu.i.read(), 0x100 + 0xC * 5);
+        assert_eq!(
+            cpu.ram.read_range(cpu.i.read(), 5).unwrap(),
+            &SPRITES[0xC * 5..0xC * 5 + 5]
+        );
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(cpu.i.read(), 0x100 + 80 + 5 * 10);
+        assert_eq!(
+            cpu.ram.read_range(cpu.i.read(), 10).unwrap(),
+            &HIRES_SPRITES[50..60]
+        );
+
+        assert_eq!(
+            cpu.set_font_base((RAM_SIZE - FONT_SIZE) as u16 + 1),
+            Err(CpuError::Memory(MemoryError::OutOfBounds))
+        );
+        assert_eq!(cpu.font_base(), 0x100);
+    }
+
+    #[test]
+    fn test_rpl_flags() {
+        let mut cpu = CPU::new();
+        // V0..V3 = 1, 2, 3, 4, store V0..V3, V0..V3 = 0, restore V0..V3
+        cpu.load_rom(&[
+            0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x63, 0x04, 0xF3, 0x75, 0x60, 0x00, 0x61, 0x00,
+            0x62, 0x00, 0x63, 0x00, 0xF3, 0x85,
+        ])
+        .unwrap();
+
+        for _ in 0..5 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.rpl_flags()[..5], [1, 2, 3, 4, 0]);
+
+        for _ in 0..5 {
+            cpu.step().unwrap();
+        }
+        for register in 0..4 {
+            assert_eq!(cpu.v.read(register).unwrap(), register + 1);
+        }
+    }
+
+    #[test]
+    fn test_rpl_flags_all_registers() {
+        let mut cpu = CPU::new();
+        // Store and restore all 16 registers.
+        cpu.load_rom(&[0xFF, 0x75, 0xFF, 0x85]).unwrap();
+
+        let pattern: Vec<u8> = (0..16).map(|r| 0xA0 | r).collect();
+        cpu.v.write_buf(0, &pattern).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.rpl_flags().to_vec(), pattern);
+
+        cpu.v.write_buf(0, &[0; 16]).unwrap();
+        cpu.step().unwrap();
+        for register in 0..16 {
+            assert_eq!(cpu.v.read(register).unwrap(), pattern[register as usize]);
+        }
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut cpu = CPU::new();
+        // V0 = 0xFE, I = 0x300, BCD of V0, V0..V1 -> RAM, CALL 0x20E, 0x20E: draw, CLS
+        cpu.load_rom(&[
+            0x60, 0xFE, 0xA3, 0x00, 0xF0, 0x33, 0xF1, 0x55, 0x22, 0x0C, 0x00, 0x00, 0xD0, 0x15,
+            0x00, 0xE0,
+        ])
+        .unwrap();
+        cpu.enable_undo(16);
+
+        assert!(!cpu.step_back());
+
+        let mut states = Vec::new();
+        for _ in 0..7 {
+            states.push(cpu.snapshot());
+            cpu.step().unwrap();
+        }
 
-                    let result = vy.wrapping_sub(vx);
-                    self.v
-                        .write(x, result as u8)
-                        .expect(&format!("Could not write {} to V({})!", result, x));
-                }
-                0xE => {
-                    let vx = self.v.read(x).expect(&format!("Could not read V({})!", x));
+        assert_eq!(cpu.ram.read(0x300).unwrap(), 0xFE);
+        assert_eq!(cpu.ram.read(0x302).unwrap(), 4);
+        assert!(cpu.screen.iter_pixels().all(|pixel| !pixel));
 
-                    trace!("Set V({}) = V({}) SHL 1", x, x);
+        while let Some(state) = states.pop() {
+            assert!(cpu.step_back());
+            assert_eq!(cpu.snapshot(), state);
+        }
 
-                    self.v.write(0xF, vx & 0x80).expect(&format!(
-                        "Could not write {} to V({})!",
-                        vx & 0x80,
-                        x
-                    ));
-                    self.v.write(x, vx << 1).expect(&format!(
-                        "Could not write {} to V({})!",
-                        vx << 1,
-                        x
-                    ));
-                }
-                x => panic!("Invalid instruction received! {}", x),
-            },
-            0x9000 => {
-                let vx = self.v.read(x).expect(&format!("Could not read V({})", x));
-                let vy = self.v.read(y).expect(&format!("Could not read V({})", y));
+        assert!(!cpu.step_back());
+    }
 
-                trace!("Skip next instruction if V({}) != V({})", x, y);
+    #[test]
+    fn test_step_back_over_mode_switch() {
+        let mut cpu = CPU::new();
+        cpu.set_quirks(Quirks::xo_chip());
+        // HIGH, select plane 2, LOW
+        cpu.load_rom(&[0x00, 0xFF, 0xF2, 0x01, 0x00, 0xFE]).unwrap();
+        cpu.enable_undo(16);
 
-                if vx != vy {
-                    trace!("Skipping next instruction");
-                    self.increment_program_counter();
-                };
-            }
-            0xA000 => {
-                let nnn = opcode & 0xFFF;
-                trace!("Set I = {}", nnn);
-                self.i.write(nnn);
-            }
-            0xB000 => {
-                let nnn = opcode & 0xFFF;
-                let v0 = self
-                    .v
-                    .read(0x0)
-                    .expect(&format!("Could not read V({})!", 0x0));
-                self.program_counter = nnn + v0 as u16;
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(cpu.screen.width(), 128);
+        assert_eq!(cpu.screen.selected_planes(), 0b10);
 
-                trace!("Jump to location {} + {} = {}", nnn, v0, nnn + v0 as u16);
-            }
-            0xC000 => {
-                let kk = (opcode & 0xFF) as u8;
-                let rand_num: u8 = rand::thread_rng().gen::<u8>();
+        cpu.step().unwrap();
+        assert_eq!(cpu.screen.width(), 64);
 
-                trace!("Set V({}) = RAND BYTE {} & {}", x, rand_num, kk);
+        assert!(cpu.step_back());
+        assert_eq!(cpu.screen.width(), 128);
 
-                self.v.write(x, rand_num & kk).expect(&format!(
-                    "Could not write {} to V({})!",
-                    rand_num & kk,
-                    x
-                ));
-            }
-            0xD000 => {
-                trace!("Display n-byte sprite starting at memory location I at (V({}), V({})), set V(0xF) = Collision {}", x, y, -1);
-                self.screen.draw();
+        assert!(cpu.step_back());
+        assert_eq!(cpu.screen.selected_planes(), 0b01);
+
+        assert!(cpu.step_back());
+        assert_eq!(cpu.program_counter, PROGRAM_START);
+        assert_eq!(cpu.screen.width(), 64);
+    }
+
+    #[test]
+    fn test_step_back_depth() {
+        let mut cpu = CPU::new();
+        // V0 += 1 forever
+        cpu.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        cpu.enable_undo(3);
+
+        for _ in 0..10 {
+            cpu.step().unwrap();
+        }
+
+        for _ in 0..3 {
+            assert!(cpu.step_back());
+        }
+        assert!(!cpu.step_back());
+
+        // Entries are not reserved up front, so an unbounded depth is fine.
+        cpu.enable_undo(usize::MAX);
+        cpu.step().unwrap();
+        assert!(cpu.step_back());
+    }
+
+    #[test]
+    fn test_load_rom_at() {
+        let mut cpu = CPU::new();
+        // V0 = 0x42
+        cpu.load_rom_at(0x600, &[0x60, 0x42]).unwrap();
+
+        assert_eq!(cpu.program_counter, 0x600);
+        assert_eq!(cpu.fetch_opcode(0x600).unwrap(), 0x6042);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.v.read(0x0).unwrap(), 0x42);
+        assert_eq!(cpu.program_counter, 0x602);
+    }
+
+    #[test]
+    fn test_load_rom_at_out_of_bounds() {
+        let mut cpu = CPU::new();
+
+        assert!(matches!(
+            cpu.load_rom_at(RAM_SIZE as u16 - 1, &[0x60, 0x42]),
+            Err(RomError::TooLarge { size: 2, max: 1 })
+        ));
+        assert!(matches!(
+            cpu.load_rom_at(RAM_SIZE as u16, &[0x60, 0x42]),
+            Err(RomError::Memory(MemoryError::OutOfBounds))
+        ));
+        assert!(matches!(
+            cpu.load_rom(&vec![0; 0x10004]),
+            Err(RomError::TooLarge {
+                size: 0x10004,
+                max: MAX_ROM_SIZE
+            })
+        ));
+        assert!(matches!(
+            cpu.load_rom_at(0x600, &vec![0; MAX_ROM_SIZE]),
+            Err(RomError::TooLarge { .. })
+        ));
+        assert_eq!(cpu.program_counter, PROGRAM_START);
+    }
+
+    #[test]
+    fn test_load_rom_too_small() {
+        let mut cpu = CPU::new();
+
+        assert!(matches!(
+            cpu.load_rom(&[]),
+            Err(RomError::TooSmall { size: 0 })
+        ));
+        assert!(matches!(
+            cpu.load_rom(&[0x60]),
+            Err(RomError::TooSmall { size: 1 })
+        ));
+        assert!(matches!(
+            cpu.load_rom_from_reader(&[][..]),
+            Err(RomError::TooSmall { size: 0 })
+        ));
+
+        // Odd lengths are accepted, the last byte is usually padding or sprite data.
+        cpu.load_rom(&[0x60, 0x42, 0xFF]).unwrap();
+        assert_eq!(cpu.ram.read(PROGRAM_START + 2).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn test_run_frame_executes_clock_speed_per_second() {
+        let mut cpu = CPU::new();
+        // V0 += 1, jump back forever.
+        cpu.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+
+        let executed: usize = (0..FRAME_RATE as usize)
+            .map(|_| cpu.run_frame().unwrap())
+            .sum();
+        assert_eq!(executed, 500);
+
+        cpu.set_clock_speed(700.);
+        let executed: usize = (0..FRAME_RATE as usize)
+            .map(|_| cpu.run_frame().unwrap())
+            .sum();
+        assert_eq!(executed, 700);
+
+        cpu.pause();
+        assert_eq!(cpu.run_frame().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_frames_tick_timers() {
+        // V0 = 60, DT = V0, ST = V0, loop forever
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x60, 0x3C, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06])
+            .unwrap();
+
+        cpu.run_frame().unwrap();
+        assert_eq!(cpu.delay_timer.read(), 59);
+        assert_eq!(cpu.sound_timer.read(), 59);
+
+        for _ in 0..59 {
+            cpu.run_frame().unwrap();
+        }
+        assert_eq!(cpu.delay_timer.read(), 0);
+        assert_eq!(cpu.sound_timer.read(), 0);
+
+        // Stepping alone never ticks them.
+        cpu.reload_rom(&[0x60, 0x3C, 0xF0, 0x15, 0x12, 0x04])
+            .unwrap();
+        cpu.run_cycles(100).unwrap();
+        assert_eq!(cpu.delay_timer.read(), 60);
+    }
+
+    #[test]
+    fn test_pause_freezes_timers() {
+        // V0 = 30, DT = V0, ST = V0
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x60, 0x1E, 0xF0, 0x15, 0xF0, 0x18]).unwrap();
+        cpu.run_cycles(3).unwrap();
+
+        cpu.pause();
+        for _ in 0..10 {
+            cpu.run_frame().unwrap();
+            cpu.tick_timers();
+        }
+        assert_eq!(cpu.delay_timer.read(), 30);
+        assert_eq!(cpu.sound_timer.read(), 30);
+
+        cpu.resume();
+        cpu.tick_timers();
+        assert_eq!(cpu.delay_timer.read(), 29);
+        assert_eq!(cpu.sound_timer.read(), 29);
+    }
+
+    #[test]
+    fn test_turbo() {
+        let mut cpu = CPU::new();
+        // DT = 60, V0 += 1, jump back to the add
+        cpu.load_rom(&[0x60, 0x3C, 0xF0, 0x15, 0x70, 0x01, 0x12, 0x04])
+            .unwrap();
+        cpu.set_clock_speed(600.);
+
+        assert_eq!(cpu.run_frame().unwrap(), 10);
+
+        cpu.set_turbo(true);
+        let executed: usize = (0..10).map(|_| cpu.run_frame().unwrap()).sum();
+        assert_eq!(executed, 10 * 40);
+        // Frames do not tick the timers any faster, every frame is one tick.
+        assert_eq!(cpu.delay_timer.read(), 60 - 11);
+
+        cpu.set_turbo_factor(0.5);
+        assert_eq!(cpu.turbo_factor(), 1.);
+        assert_eq!(cpu.run_frame().unwrap(), 10);
+
+        cpu.set_turbo(false);
+        assert_eq!(cpu.run_frame().unwrap(), 10);
+    }
+
+    #[test]
+    fn test_display_wait_quirk() {
+        // Draw, V1 += 1, jump back to the draw
+        let program = [0xD0, 0x11, 0x71, 0x01, 0x12, 0x00];
+
+        let mut cpu = CPU::new();
+        cpu.set_quirks(Quirks::cosmac_vip());
+        cpu.load_rom(&program).unwrap();
+
+        assert_eq!(cpu.run_frame().unwrap(), 1);
+        for _ in 1..FRAME_RATE as usize {
+            assert_eq!(cpu.run_frame().unwrap(), 3);
+        }
+        // One draw per frame, V1 counts the draws after the first.
+        assert_eq!(cpu.v.read(1).unwrap(), FRAME_RATE as u8 - 1);
+
+        let mut cpu = CPU::new();
+        cpu.set_quirks(Quirks::super_chip());
+        cpu.load_rom(&program).unwrap();
+
+        for _ in 0..FRAME_RATE as usize {
+            cpu.run_frame().unwrap();
+        }
+        assert!(cpu.v.read(1).unwrap() > FRAME_RATE as u8);
+    }
+
+    #[test]
+    fn test_halt_on_invalid_opcode() {
+        let mut cpu = CPU::new();
+        let halted = cpu.on_halt();
+        // V0 = 1, then 0xE000 which is not an instruction.
+        cpu.load_rom(&[0x60, 0x01, 0xE0, 0x00]).unwrap();
+
+        cpu.step().unwrap();
+        assert!(halted.try_recv().is_err());
+
+        assert_eq!(cpu.step(), Err(CpuError::InvalidOpcode(0xE000)));
+        assert_eq!(
+            halted.try_recv().unwrap(),
+            CpuHalted {
+                reason: HaltReason::Error(CpuError::InvalidOpcode(0xE000)),
+                program_counter: PROGRAM_START + 2,
             }
-            0xE000 => {
-                match opcode & 0xFF {
-                    0x9E => {
-                        trace!(
-                            "Skip next instruction if key with the value of V({}) is pressed",
-                            x
-                        );
-                        if self.keyboard.is_key_pressed(
-                            self.v.read(x).expect(&format!("Could not read V({})!", x)),
-                        ) {
-                            trace!("Skipping next instruction");
-                            self.increment_program_counter();
-                        };
-                    }
-                    0xA1 => {
-                        trace!(
-                            "Skip next instruction if key with the value of V({}) is not pressed",
-                            x
-                        );
-                        if !self.keyboard.is_key_pressed(
-                            self.v.read(x).expect(&format!("Could not read V({})!", x)),
-                        ) {
-                            trace!("Skipping next instruction");
-                            self.increment_program_counter();
-                        };
-                    }
-                    x => panic!("Invalid instruction received! {}", x),
-                }
+        );
+        assert!(cpu.is_paused());
+
+        // Dropped receivers are forgotten.
+        drop(halted);
+        cpu.resume();
+        cpu.program_counter = PROGRAM_START + 2;
+        assert!(cpu.step().is_err());
+        assert!(cpu.halt_listeners.is_empty());
+    }
+
+    #[test]
+    fn test_idle_on_jump_to_self() {
+        let mut cpu = CPU::new();
+        let halted = cpu.on_halt();
+        // V0 = 1, jump to 0x204, jump to self
+        cpu.load_rom(&[0x60, 0x01, 0x12, 0x04, 0x12, 0x04]).unwrap();
+
+        cpu.run_cycles(2).unwrap();
+        assert!(!cpu.is_idle());
+        assert!(halted.try_recv().is_err());
+
+        cpu.run_cycles(1).unwrap();
+        assert!(cpu.is_idle());
+        assert!(!cpu.is_halted());
+        assert!(!cpu.is_paused());
+        assert_eq!(
+            halted.try_recv().unwrap(),
+            CpuHalted {
+                reason: HaltReason::Idle,
+                program_counter: PROGRAM_START + 4,
             }
-            0xF000 => {
-                match opcode & 0xFF {
-                    0x07 => {
-                        let delaytimer_value = self.delay_timer.read();
-                        trace!("Write delaytimer {} into V({})", delaytimer_value, x);
+        );
+        assert_eq!(cpu.run_frame().unwrap(), 0);
+        assert_eq!(cpu.program_counter, PROGRAM_START + 4);
 
-                        self.v.write(x, delaytimer_value).expect(&format!(
-                            "Could not write delaytimer {} into v({})!",
-                            delaytimer_value, x
-                        ));
-                    }
-                    0x0F => {
-                        let delay_timer = self.delay_timer.read();
-                        trace!("Set V({}) = Delay Timer {}", x, delay_timer);
-                        self.v.write(x, self.delay_timer.read()).expect(&format!(
-                            "Could not write {} to V({})!",
-                            self.delay_timer.read(),
-                            x
-                        ))
-                    }
-                    0x0A => {
-                        self.is_paused = true;
+        cpu.reset();
+        assert!(!cpu.is_idle());
+    }
 
-                        trace!("Wait for a key press");
+    #[test]
+    fn test_run_cycles() {
+        // V0 = 5, V1 = 3, V0 += V1, I = sprite(V0), draw at (V2, V2), jump to self
+        let program = [
+            0x60, 0x05, 0x61, 0x03, 0x80, 0x14, 0xF0, 0x29, 0xD2, 0x25, 0x12, 0x0A,
+        ];
 
-                        let key = self.keyboard.wait_for_key();
-                        self.v
-                            .write(x, key)
-                            .expect(&format!("Could not write {} to V({})!", key, x));
+        let mut cpu = CPU::new();
+        cpu.load_rom(&program).unwrap();
 
-                        trace!(
-                            "Key {} pressed, stored the value of the key in V({})",
-                            key,
-                            x
-                        );
+        cpu.run_cycles(3).unwrap();
+        assert_eq!(cpu.v.read(0).unwrap(), 8);
+        assert_eq!(cpu.program_counter, PROGRAM_START + 6);
+        assert!(cpu.screen.iter_pixels().all(|pixel| !pixel));
 
-                        self.is_paused = false;
-                    }
-                    0x15 => {
-                        trace!("Set delay timer = V({})", x);
-                        self.delay_timer
-                            .write(self.v.read(x).expect(&format!("Could not read V({})!", x)));
-                    }
-                    0x18 => {
-                        trace!("Set sound timer = V({})", x);
-                        self.sound_timer
-                            .write(self.v.read(x).expect(&format!("Could not read V({})!", x)));
-                    }
-                    0x1E => {
-                        trace!("Set I = I{} + V({})", self.i.read(), x);
-                        self.i.write(self.i.read().wrapping_add(
-                            self.v.read(x).expect(&format!("Could not read V({})!", x)) as u16,
-                        ));
-                    }
-                    0x29 => {
-                        trace!("Set I = location of sprite for digit V({})", x);
-                        self.i.write(
-                            self.v.read(x).expect(&format!("Could not read V({})!", x)) as u16 * 5,
-                        );
-                    }
-                    0x33 => {
-                        let i = self.i.read();
-                        trace!("Store BCD representation of V({}) in memory locations I{}, I{}+1, and I{}+2", x, i, i, i);
+        cpu.run_cycles(100).unwrap();
+        assert_eq!(cpu.program_counter, PROGRAM_START + 10);
+        // Top row of the 8 is 0xF0.
+        for x in 0..8 {
+            assert_eq!(cpu.screen.get_pixel(x, 0), Some(x < 4));
+        }
+        assert_eq!(cpu.screen.iter_pixels().filter(|&pixel| pixel).count(), 16);
 
-                        self.ram
-                            .write(
-                                i,
-                                // Get hundrets digit.
-                                self.v.read(x).expect(&format!("Could not read V({})!", x)) / 100,
-                            )
-                            .expect(&format!("Could not write RAM({})!", x));
-
-                        self.ram
-                            .write(
-                                i.checked_add(1)
-                                    .expect(&format!("Could not add 1 to I {}!", i)),
-                                // Get value of the tens digit.
-                                (self.v.read(x).expect(&format!("Could not read V({})!", x)) % 100)
-                                    / 10,
-                            )
-                            .expect(&format!("Could not write RAM({})!", x));
-
-                        self.ram
-                            .write(
-                                i.checked_add(2)
-                                    .expect(&format!("Could not add 2 to I {}!", i)),
-                                // Get value of the ones digit
-                                self.v.read(x).expect(&format!("Could not read V({})!", x)) % 10,
-                            )
-                            .expect(&format!("Could not write RAM({})!", x));
-                    }
-                    0x55 => {
-                        let i = self.i.read();
-                        trace!(
-                            "Store registers V(0) through V({}) in memory starting at location I{}",
-                            x,
-                            i
-                        );
-                        self.ram
-                            .write_buf(
-                                i,
-                                self.v
-                                    .read_range(0, x)
-                                    .expect(&format!("Could not read range V(0, {})!", x)),
-                            )
-                            .expect(&format!(
-                                "Could not write V(0, {}) in RAM({}, {})!",
-                                x,
-                                i,
-                                i + x as u16
-                            ))
-                    }
-                    0x65 => {
-                        let i = self.i.read();
-                        trace!("Read registers V(0) through V({}) from memory starting at location I{}", x, i);
-                        self.v
-                            .write_buf(
-                                0,
-                                self.ram.read_range(i, x as u16).expect(&format!(
-                                    "Could not read range from RAM({}, {})!",
-                                    i, x
-                                )),
-                            )
-                            .expect(&format!("Could not write RAM({}, {}) to V(0)!", i, x))
-                    }
-                    x => panic!("Invalid instruction received! {}", x),
-                }
+        cpu.pause();
+        cpu.program_counter = PROGRAM_START;
+        cpu.run_cycles(1).unwrap();
+        assert_eq!(cpu.program_counter, PROGRAM_START);
+    }
+
+    #[test]
+    fn test_exit() {
+        let mut cpu = CPU::new();
+        let halted = cpu.on_halt();
+        // V0 = 1, EXIT, V0 = 2
+        cpu.load_rom(&[0x60, 0x01, 0x00, 0xFD, 0x60, 0x02]).unwrap();
+
+        assert_eq!(cpu.run_frame().unwrap(), 2);
+        assert!(cpu.is_halted());
+        assert!(!cpu.is_paused());
+        assert_eq!(
+            halted.try_recv().unwrap(),
+            CpuHalted {
+                reason: HaltReason::Exit,
+                program_counter: PROGRAM_START + 2,
             }
-            x => panic!("Invalid instruction received! {}", x),
-        };
+        );
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.v.read(0).unwrap(), 1);
+        assert_eq!(cpu.clock(), Ok(()));
+
+        cpu.reset();
+        assert!(!cpu.is_halted());
     }
 
-    pub fn clock(&mut self) {
-        let clock_duration = Duration::from_secs_f64(1. / self.clock_speed);
+    #[test]
+    fn test_clock_paused_sleeps() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x12, 0x00]).unwrap();
+        cpu.pause();
 
-        loop {
-            let start = Instant::now();
+        let start = Instant::now();
+        let wake_ups = cpu.clock_for(Duration::from_millis(200)).unwrap();
 
-            if !self.is_paused {
-                self.cycle();
-            };
+        // About 12 frames, a spinning loop would wake up orders of magnitude more often.
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(wake_ups <= 14, "{} wake-ups", wake_ups);
+        assert_eq!(cpu.stats().instruction_count, 0);
 
-            if let Some(waiting_duration) = clock_duration.checked_sub(start.elapsed()) {
-                trace!("Waiting {} ns", waiting_duration.as_nanos());
-                thread::sleep(waiting_duration);
-            };
+        cpu.resume();
+        cpu.clock_for(Duration::from_millis(50)).unwrap();
+        assert!(cpu.stats().instruction_count > 0);
+    }
+
+    #[test]
+    fn test_invalid_opcode_policy() {
+        // V0 = 1, invalid opcode, V1 = 2
+        let program = [0x60, 0x01, 0xE0, 0x00, 0x61, 0x02];
+
+        let mut cpu = run(&program, 1);
+        assert_eq!(cpu.invalid_opcode_policy(), InvalidOpcodePolicy::Halt);
+        assert_eq!(cpu.step(), Err(CpuError::InvalidOpcode(0xE000)));
+        assert!(cpu.is_paused());
+
+        for policy in [InvalidOpcodePolicy::Skip, InvalidOpcodePolicy::Nop] {
+            let mut cpu = CPU::new();
+            let halted = cpu.on_halt();
+            cpu.set_invalid_opcode_policy(policy);
+            cpu.load_rom(&program).unwrap();
+
+            cpu.run_cycles(2).unwrap();
+            assert_eq!(cpu.program_counter, PROGRAM_START + 4);
+            assert!(!cpu.is_paused());
+            assert!(halted.try_recv().is_err());
+
+            cpu.step().unwrap();
+            assert_eq!(cpu.v.read(1).unwrap(), 2);
         }
     }
 
-    fn increment_program_counter(&mut self) {
-        self.program_counter += 2;
-        trace!("Incremented Program Counter.");
+    #[test]
+    fn test_skip_register_low_nibble() {
+        // V0 = 1, V1 = 1, skip if V0 == V1, V2 = 1 (skipped), skip if V0 != V1
+        let cpu = run(
+            &[0x60, 0x01, 0x61, 0x01, 0x50, 0x10, 0x62, 0x01, 0x90, 0x10],
+            4,
+        );
+        assert_eq!(cpu.v.read(2).unwrap(), 0);
+        assert_eq!(cpu.program_counter, PROGRAM_START + 10);
+
+        // 5xy2 and 5xy3 only exist in XO-CHIP.
+        for opcode in [0x5AB4, 0x5012, 0x5013, 0x901F] {
+            let mut cpu = CPU::new();
+            cpu.load_rom(&u16::to_be_bytes(opcode)).unwrap();
+            assert_eq!(cpu.step(), Err(CpuError::InvalidOpcode(opcode)));
+        }
+    }
+
+    #[test]
+    fn test_register_range_save_load() {
+        // V1 = 1, V2 = 2, V3 = 3, I = 0x300, store V1..V3, store V3..V1 at 0x310
+        let program = [
+            0x61, 0x01, 0x62, 0x02, 0x63, 0x03, 0xA3, 0x00, 0x51, 0x32, 0xA3, 0x10, 0x53, 0x12,
+        ];
+        let cpu = run_with_quirks(&program, 7, Quirks::xo_chip());
+        assert_eq!(cpu.ram.read_range(0x300, 4).unwrap(), &[1, 2, 3, 0]);
+        assert_eq!(cpu.ram.read_range(0x310, 4).unwrap(), &[3, 2, 1, 0]);
+        assert_eq!(cpu.i.read(), 0x310);
+
+        // I = 0x300, load V5..V7, load V9..V8 (a descending range of two)
+        let mut cpu = CPU::new();
+        cpu.set_quirks(Quirks::xo_chip());
+        cpu.ram.write_buf(0x300, &[0xA, 0xB, 0xC]).unwrap();
+        cpu.load_rom(&[0xA3, 0x00, 0x55, 0x73, 0x59, 0x83]).unwrap();
+        cpu.run_cycles(3).unwrap();
+
+        assert_eq!(cpu.v.read_range(5, 3).unwrap(), &[0xA, 0xB, 0xC]);
+        assert_eq!(cpu.v.read(9).unwrap(), 0xA);
+        assert_eq!(cpu.v.read(8).unwrap(), 0xB);
+        assert_eq!(cpu.i.read(), 0x300);
+
+        // Not available without the XO-CHIP instructions.
+        let mut cpu = run(&[0xA3, 0x00], 1);
+        cpu.load_rom(&[0x51, 0x32]).unwrap();
+        assert_eq!(cpu.step(), Err(CpuError::InvalidOpcode(0x5132)));
+    }
+
+    #[test]
+    fn test_save_load_range_at_end_of_ram() {
+        // V0 = 0xA0, V1 = 0xA1, V2 = 0xA2, I = 0xFFE, save V0..V2
+        let mut cpu = CPU::new();
+        cpu.set_quirks(Quirks::xo_chip());
+        cpu.load_rom(&[0x60, 0xA0, 0x61, 0xA1, 0x62, 0xA2, 0xAF, 0xFE, 0x50, 0x22])
+            .unwrap();
+        cpu.run_cycles(4).unwrap();
+        assert_eq!(cpu.step(), Err(CpuError::OutOfBounds(0xFFE)));
+        assert_eq!(cpu.ram.read_range(0xFFE, 2).unwrap(), &[0, 0]);
+
+        // V0 = 0xA0, V1 = 0xA1, V2 = 0xA2, I = 0xFFE, load V0..V2
+        let mut cpu = CPU::new();
+        cpu.set_quirks(Quirks::xo_chip());
+        cpu.ram.write_buf(0xFFE, &[0xB0, 0xB1]).unwrap();
+        cpu.load_rom(&[0x60, 0xA0, 0x61, 0xA1, 0x62, 0xA2, 0xAF, 0xFE, 0x50, 0x23])
+            .unwrap();
+        cpu.run_cycles(4).unwrap();
+        assert_eq!(cpu.step(), Err(CpuError::OutOfBounds(0xFFE)));
+        assert_eq!(cpu.v.read_range(0, 3).unwrap(), &[0xA0, 0xA1, 0xA2]);
+    }
+
+    #[test]
+    fn test_audio_pattern() {
+        let pattern: Vec<u8> = (0..PATTERN_SIZE as u8).collect();
+        let mut cpu = CPU::new();
+        cpu.set_quirks(Quirks::xo_chip());
+        cpu.ram.write_buf(0x300, &pattern).unwrap();
+        // I = 0x300, load the pattern, V0 = 112, set the pitch to V0
+        cpu.load_rom(&[0xA3, 0x00, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A])
+            .unwrap();
+        cpu.run_cycles(4).unwrap();
+
+        let loaded = cpu.sound_timer().pattern().unwrap();
+        assert_eq!(loaded.buffer.as_slice(), pattern.as_slice());
+        assert_eq!(loaded.playback_rate(), 8000.);
+
+        cpu.reset();
+        assert_eq!(cpu.sound_timer().pattern(), None);
+
+        // Plain CHIP-8 does not know F002.
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0xF0, 0x02]).unwrap();
+        assert_eq!(cpu.step(), Err(CpuError::InvalidOpcode(0xF002)));
+    }
+
+    #[test]
+    fn test_unsupported_opcodes() {
+        // V0 = 1, invalid, 5xy1, invalid again, V1 = 2
+        let program = [0x60, 0x01, 0xE0, 0x00, 0x50, 0x11, 0xE0, 0x00, 0x61, 0x02];
+
+        let mut cpu = CPU::new();
+        cpu.set_invalid_opcode_policy(InvalidOpcodePolicy::Nop);
+        cpu.load_rom(&program).unwrap();
+        cpu.run_cycles(5).unwrap();
+
+        assert_eq!(cpu.v.read(1).unwrap(), 2);
+        assert_eq!(cpu.unsupported_opcodes(), &HashSet::from([0xE000, 0x5011]));
+
+        cpu.reset();
+        assert!(cpu.unsupported_opcodes().is_empty());
+    }
+
+    #[test]
+    fn test_rng_override() {
+        // V0 = random & 0x0F, V1 = random & 0xFF
+        let program = [0xC0, 0x0F, 0xC1, 0xFF];
+
+        let mut cpu = CPU::with_seed(3);
+        cpu.set_rng_override(Some(0xA5));
+        cpu.load_rom(&program).unwrap();
+        cpu.run_cycles(2).unwrap();
+
+        assert_eq!(cpu.v.read(0).unwrap(), 0xA5 & 0x0F);
+        assert_eq!(cpu.v.read(1).unwrap(), 0xA5);
+
+        // The override does not draw from the generator, so it continues like a fresh one.
+        cpu.set_rng_override(None);
+        cpu.reload_rom(&program).unwrap();
+        cpu.run_cycles(2).unwrap();
+
+        let mut fresh = CPU::with_seed(3);
+        fresh.load_rom(&program).unwrap();
+        fresh.run_cycles(2).unwrap();
+        assert_eq!(cpu.v.read(1), fresh.v.read(1));
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        // invalid, I = 0x100, store V0 at I, 0nnn to the next instruction, I = 0xFFF, I += V0
+        let program = [
+            0xE0, 0x00, 0xA1, 0x00, 0xF0, 0x55, 0x02, 0x08, 0xAF, 0xFF, 0x60, 0x01, 0xF0, 0x1E,
+        ];
+        let run_program = |strict| {
+            let mut cpu = CPU::new();
+            cpu.set_strict(strict);
+            cpu.set_invalid_opcode_policy(InvalidOpcodePolicy::Skip);
+            cpu.load_rom(&program).unwrap();
+            cpu.run_cycles(7).unwrap();
+            cpu
+        };
+
+        assert!(run_program(false).deviations().is_empty());
+
+        let mut cpu = run_program(true);
+        assert_eq!(
+            cpu.deviations(),
+            [
+                "Stepped over invalid opcode 0xE000 at 0x200",
+                "Write to the interpreter area at 0x100",
+                "Machine code call 0x0208 treated as a jump",
+                "I left the address space with 0x1000",
+            ]
+        );
+
+        cpu.reset();
+        assert!(cpu.deviations().is_empty());
+    }
+
+    #[test]
+    fn test_call_graph() {
+        // 0x200: CALL 0x20A, CALL 0x210, jump to self
+        // 0x20A: CALL 0x210, RET
+        // 0x210: RET
+        let program = [
+            0x22, 0x0A, 0x22, 0x10, 0x12, 0x04, 0x00, 0x00, 0x00, 0x00, 0x22, 0x10, 0x00, 0xEE,
+            0x00, 0x00, 0x00, 0xEE,
+        ];
+
+        let mut cpu = CPU::new();
+        cpu.load_rom(&program).unwrap();
+        cpu.run_cycles(3).unwrap();
+        assert!(cpu.call_graph().is_empty());
+
+        cpu.reset();
+        cpu.enable_call_graph();
+        cpu.load_rom(&program).unwrap();
+        cpu.run_cycles(100).unwrap();
+
+        assert_eq!(cpu.call_graph().len(), 2);
+        assert_eq!(cpu.call_graph()[&0x20A], [0x200]);
+        assert_eq!(cpu.call_graph()[&0x210], [0x20A, 0x202]);
+
+        cpu.disable_call_graph();
+        assert!(cpu.call_graph().is_empty());
+    }
+
+    #[test]
+    fn test_opcode_histogram() {
+        let mut cpu = CPU::new();
+        // V0 += 1, V1 = 5, loop back to the start
+        cpu.load_rom(&[0x70, 0x01, 0x61, 0x05, 0x12, 0x00]).unwrap();
+
+        cpu.run_cycles(3).unwrap();
+        assert!(cpu.opcode_histogram().is_empty());
+
+        cpu.enable_opcode_histogram();
+        cpu.run_cycles(7).unwrap();
+
+        let histogram = cpu.opcode_histogram();
+        assert_eq!(histogram.len(), 3);
+        assert_eq!(histogram[&0x7001], 3);
+        assert_eq!(histogram[&0x6105], 2);
+        assert_eq!(histogram[&0x1200], 2);
+
+        cpu.disable_opcode_histogram();
+        assert!(cpu.opcode_histogram().is_empty());
+    }
+
+    #[test]
+    fn test_watch_register() {
+        let mut cpu = CPU::new();
+        cpu.watch_register(Register::V3);
+        // V3 = 7, V4 = 1, V3 += V4, V3 += 0
+        cpu.load_rom(&[0x63, 0x07, 0x64, 0x01, 0x83, 0x44, 0x73, 0x00])
+            .unwrap();
+
+        cpu.run_cycles(4).unwrap();
+        assert_eq!(
+            cpu.register_changes(),
+            &[
+                RegisterChange {
+                    register: Register::V3,
+                    old: 0,
+                    new: 7,
+                    program_counter: PROGRAM_START,
+                },
+                RegisterChange {
+                    register: Register::V3,
+                    old: 7,
+                    new: 8,
+                    program_counter: PROGRAM_START + 4,
+                },
+            ]
+        );
+
+        cpu.clear_register_changes();
+        cpu.unwatch_register(Register::V3);
+        cpu.reload_rom(&[0x63, 0x07]).unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.register_changes().is_empty());
+    }
+
+    #[test]
+    fn test_resolution_switch() {
+        // high-res, low-res
+        let mut cpu = run(&[0x00, 0xFF, 0x00, 0xFE], 1);
+        assert_eq!(cpu.screen.mode(), ScreenMode::HiRes);
+        assert_eq!(cpu.program_counter, PROGRAM_START + 2);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.screen.mode(), ScreenMode::LoRes);
+    }
+
+    #[test]
+    fn test_seeded_random() {
+        // V0..V3 = RAND & 0xFF
+        let program = [0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF, 0xC3, 0xFF];
+
+        let randoms = |seed| {
+            let mut cpu = CPU::with_seed(seed);
+            cpu.load_rom(&program).unwrap();
+            for _ in 0..4 {
+                cpu.step().unwrap();
+            }
+
+            (0..4)
+                .map(|register| cpu.v.read(register).unwrap())
+                .collect::<Vec<u8>>()
+        };
+
+        assert_eq!(randoms(42), randoms(42));
+        assert_ne!(randoms(42), randoms(43));
     }
 }
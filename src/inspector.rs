@@ -0,0 +1,226 @@
+use std::fmt::Write as _;
+
+use log::{info, trace};
+
+use crate::{
+    cpu::{CpuError, CPU},
+    io::{MemoryError, Read, Write},
+    ram::RAM_SIZE,
+    registers::Register,
+};
+
+/// Bytes shown per line of the hex view.
+pub const BYTES_PER_ROW: usize = 16;
+
+/// A change to the machine state requested from the inspector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InspectorEdit {
+    Memory { address: u16, value: u8 },
+    Register { register: Register, value: u8 },
+    I(u16),
+    ProgramCounter(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InspectorError {
+    /// Edits are only allowed while the CPU is paused.
+    NotPaused,
+    Memory(MemoryError),
+    Cpu(CpuError),
+}
+
+impl From<MemoryError> for InspectorError {
+    fn from(error: MemoryError) -> Self {
+        InspectorError::Memory(error)
+    }
+}
+
+impl From<CpuError> for InspectorError {
+    fn from(error: CpuError) -> Self {
+        InspectorError::Cpu(error)
+    }
+}
+
+/// State of a debug panel showing RAM, registers, stack and timers.
+/// It does not draw anything itself, a front-end lays out the text it produces and routes the
+/// user's edits back through `apply`.
+#[derive(Debug)]
+pub struct Inspector {
+    // First visible row of the hex view.
+    scroll: usize,
+}
+impl Inspector {
+    pub fn new() -> Self {
+        Inspector { scroll: 0 }
+    }
+
+    pub fn scroll(&self) -> usize {
+        self.scroll
+    }
+
+    /// Scrolls the hex view so `row` is at the top, clamped to the last row.
+    pub fn scroll_to(&mut self, row: usize) {
+        self.scroll = row.min(Self::row_count() - 1);
+    }
+
+    /// Scrolls the hex view so `address` is visible.
+    pub fn scroll_to_address(&mut self, address: u16) {
+        self.scroll_to(address as usize / BYTES_PER_ROW);
+    }
+
+    /// Number of rows the whole RAM takes up in the hex view.
+    pub fn row_count() -> usize {
+        RAM_SIZE / BYTES_PER_ROW
+    }
+
+    /// Formats up to `count` rows of RAM starting at the scroll position,
+    /// e.g. `0200: 00 E0 A2 2A ...`.
+    pub fn hex_rows(&self, cpu: &CPU, count: usize) -> Vec<String> {
+        (self.scroll..Self::row_count().min(self.scroll + count))
+            .map(|row| {
+                let address = (row * BYTES_PER_ROW) as u16;
+                let mut line = format!("{:04X}:", address);
+
+                for offset in 0..BYTES_PER_ROW as u16 {
+                    let byte = cpu
+                        .ram()
+                        .read(address + offset)
+                        .expect(&format!("Could not read RAM({})!", address + offset));
+                    write!(line, " {:02X}", byte).expect("Could not format RAM!");
+                }
+
+                line
+            })
+            .collect()
+    }
+
+    /// Formats PC, I, the V registers, the stack and both timers, one per line.
+    pub fn registers(&self, cpu: &CPU) -> Vec<String> {
+        let mut lines = vec![
+            format!("PC: {:04X}", cpu.program_counter()),
+            format!("I:  {:04X}", cpu.index_register().read()),
+        ];
+
+        lines.extend(
+            Register::ALL
+                .iter()
+                .map(|&register| format!("{:?}: {:02X}", register, cpu.registers().get(register))),
+        );
+
+        let stack: Vec<String> = cpu
+            .stack()
+            .frames()
+            .iter()
+            .map(|frame| format!("{:04X}", frame))
+            .collect();
+        lines.push(format!("Stack: [{}]", stack.join(", ")));
+        lines.push(format!("DT: {:02X}", cpu.delay_timer().read()));
+        lines.push(format!("ST: {:02X}", cpu.sound_timer().read()));
+
+        lines
+    }
+
+    /// Writes `edit` into `cpu`. Fails unless the CPU is paused, so edits cannot race a running
+    /// program.
+    pub fn apply(&self, cpu: &mut CPU, edit: InspectorEdit) -> Result<(), InspectorError> {
+        if !cpu.is_paused() {
+            trace!("Rejected {:?}, CPU is running.", edit);
+            return Err(InspectorError::NotPaused);
+        };
+
+        info!("Applying {:?}", edit);
+
+        match edit {
+            InspectorEdit::Memory { address, value } => cpu.ram_mut().write(address, value)?,
+            InspectorEdit::Register { register, value } => {
+                cpu.registers_mut().write(register.into(), value)?
+            }
+            InspectorEdit::I(value) => cpu.index_register_mut().write(value),
+            InspectorEdit::ProgramCounter(value) => cpu.set_program_counter(value)?,
+        };
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod inspector_tests {
+    use super::*;
+
+    #[test]
+    fn test_inspector_without_window() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x00, 0xE0, 0xA2, 0x2A]).unwrap();
+
+        let mut inspector = Inspector::new();
+        inspector.scroll_to_address(0x200);
+
+        let rows = inspector.hex_rows(&cpu, 2);
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].starts_with("0200: 00 E0 A2 2A 00"));
+        assert!(rows[1].starts_with("0210: 00"));
+
+        inspector.scroll_to(usize::MAX);
+        assert_eq!(inspector.hex_rows(&cpu, 4).len(), 1);
+
+        let registers = inspector.registers(&cpu);
+        assert_eq!(registers[0], "PC: 0200");
+        assert!(registers.contains(&"VF: 00".to_string()));
+        assert!(registers.contains(&"Stack: []".to_string()));
+    }
+
+    #[test]
+    fn test_apply_only_while_paused() {
+        let mut cpu = CPU::new();
+        let inspector = Inspector::new();
+        let edit = InspectorEdit::Register {
+            register: Register::V3,
+            value: 0x42,
+        };
+
+        assert_eq!(
+            inspector.apply(&mut cpu, edit),
+            Err(InspectorError::NotPaused)
+        );
+        assert_eq!(cpu.registers().get(Register::V3), 0);
+
+        cpu.pause();
+        inspector.apply(&mut cpu, edit).unwrap();
+        inspector
+            .apply(
+                &mut cpu,
+                InspectorEdit::Memory {
+                    address: 0x300,
+                    value: 0xAB,
+                },
+            )
+            .unwrap();
+        inspector.apply(&mut cpu, InspectorEdit::I(0x300)).unwrap();
+        inspector
+            .apply(&mut cpu, InspectorEdit::ProgramCounter(0x204))
+            .unwrap();
+
+        assert_eq!(cpu.registers().get(Register::V3), 0x42);
+        assert_eq!(cpu.ram().read(0x300).unwrap(), 0xAB);
+        assert_eq!(cpu.index_register().read(), 0x300);
+        assert_eq!(cpu.program_counter(), 0x204);
+
+        assert_eq!(
+            inspector.apply(
+                &mut cpu,
+                InspectorEdit::Memory {
+                    address: RAM_SIZE as u16,
+                    value: 0
+                }
+            ),
+            Err(InspectorError::Memory(MemoryError::OutOfBounds))
+        );
+        assert_eq!(
+            inspector.apply(&mut cpu, InspectorEdit::ProgramCounter(0xFFFF)),
+            Err(InspectorError::Cpu(CpuError::ProgramCounterOutOfBounds(
+                0xFFFF
+            )))
+        );
+        assert_eq!(cpu.program_counter(), 0x204);
+    }
+}
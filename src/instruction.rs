@@ -0,0 +1,287 @@
+use std::fmt;
+
+use crate::cpu::CpuError;
+
+/// A decoded opcode, e.g. for a disassembly view. `x` and `y` are register indices.
+///
+/// Decoding does not look at the quirks, so the XO-CHIP instructions decode even when the CPU
+/// would reject them, and `JumpOffset` keeps `x` for the `jump_uses_vx` quirk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0nnn
+    MachineCall { address: u16 },
+    /// 00E0
+    Clear,
+    /// 00EE
+    Return,
+    /// 00FD
+    Exit,
+    /// 00FE
+    LoRes,
+    /// 00FF
+    HiRes,
+    /// 1nnn
+    Jump { address: u16 },
+    /// 2nnn
+    Call { address: u16 },
+    /// 3xkk
+    SkipEqualByte { x: u8, byte: u8 },
+    /// 4xkk
+    SkipNotEqualByte { x: u8, byte: u8 },
+    /// 5xy0
+    SkipEqual { x: u8, y: u8 },
+    /// 5xy2
+    SaveRange { x: u8, y: u8 },
+    /// 5xy3
+    LoadRange { x: u8, y: u8 },
+    /// 6xkk
+    LoadByte { x: u8, byte: u8 },
+    /// 7xkk
+    AddByte { x: u8, byte: u8 },
+    /// 8xy0
+    Load { x: u8, y: u8 },
+    /// 8xy1
+    Or { x: u8, y: u8 },
+    /// 8xy2
+    And { x: u8, y: u8 },
+    /// 8xy3
+    Xor { x: u8, y: u8 },
+    /// 8xy4
+    Add { x: u8, y: u8 },
+    /// 8xy5
+    Sub { x: u8, y: u8 },
+    /// 8xy6
+    ShiftRight { x: u8, y: u8 },
+    /// 8xy7
+    SubN { x: u8, y: u8 },
+    /// 8xyE
+    ShiftLeft { x: u8, y: u8 },
+    /// 9xy0
+    SkipNotEqual { x: u8, y: u8 },
+    /// Annn
+    LoadI { address: u16 },
+    /// Bnnn
+    JumpOffset { x: u8, address: u16 },
+    /// Cxkk
+    Random { x: u8, byte: u8 },
+    /// Dxyn
+    Draw { x: u8, y: u8, n: u8 },
+    /// Ex9E
+    SkipKeyPressed { x: u8 },
+    /// ExA1
+    SkipKeyNotPressed { x: u8 },
+    /// Fn01
+    SelectPlanes { planes: u8 },
+    /// F002
+    LoadPattern,
+    /// Fx07, also Fx0F
+    LoadDelay { x: u8 },
+    /// Fx0A
+    WaitKey { x: u8 },
+    /// Fx15
+    SetDelay { x: u8 },
+    /// Fx18
+    SetSound { x: u8 },
+    /// Fx1E
+    AddI { x: u8 },
+    /// Fx29
+    LoadFont { x: u8 },
+    /// Fx30
+    LoadHiResFont { x: u8 },
+    /// Fx33
+    StoreBcd { x: u8 },
+    /// Fx3A
+    SetPitch { x: u8 },
+    /// Fx55
+    StoreRegisters { x: u8 },
+    /// Fx65
+    LoadRegisters { x: u8 },
+    /// Fx75
+    StoreFlags { x: u8 },
+    /// Fx85
+    LoadFlags { x: u8 },
+}
+impl Instruction {
+    /// Decodes `opcode`, failing with `CpuError::InvalidOpcode` if it is no instruction.
+    pub fn decode(opcode: u16) -> Result<Self, CpuError> {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let byte = (opcode & 0x00FF) as u8;
+        let address = opcode & 0x0FFF;
+
+        let instruction = match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00E0 => Self::Clear,
+                0x00EE => Self::Return,
+                0x00FD => Self::Exit,
+                0x00FE => Self::LoRes,
+                0x00FF => Self::HiRes,
+                _ => Self::MachineCall { address },
+            },
+            0x1000 => Self::Jump { address },
+            0x2000 => Self::Call { address },
+            0x3000 => Self::SkipEqualByte { x, byte },
+            0x4000 => Self::SkipNotEqualByte { x, byte },
+            0x5000 => match n {
+                0x0 => Self::SkipEqual { x, y },
+                0x2 => Self::SaveRange { x, y },
+                0x3 => Self::LoadRange { x, y },
+                _ => return Err(CpuError::InvalidOpcode(opcode)),
+            },
+            0x6000 => Self::LoadByte { x, byte },
+            0x7000 => Self::AddByte { x, byte },
+            0x8000 => match n {
+                0x0 => Self::Load { x, y },
+                0x1 => Self::Or { x, y },
+                0x2 => Self::And { x, y },
+                0x3 => Self::Xor { x, y },
+                0x4 => Self::Add { x, y },
+                0x5 => Self::Sub { x, y },
+                0x6 => Self::ShiftRight { x, y },
+                0x7 => Self::SubN { x, y },
+                0xE => Self::ShiftLeft { x, y },
+                _ => return Err(CpuError::InvalidOpcode(opcode)),
+            },
+            0x9000 if n == 0 => Self::SkipNotEqual { x, y },
+            0xA000 => Self::LoadI { address },
+            0xB000 => Self::JumpOffset { x, address },
+            0xC000 => Self::Random { x, byte },
+            0xD000 => Self::Draw { x, y, n },
+            0xE000 => match byte {
+                0x9E => Self::SkipKeyPressed { x },
+                0xA1 => Self::SkipKeyNotPressed { x },
+                _ => return Err(CpuError::InvalidOpcode(opcode)),
+            },
+            0xF000 => match byte {
+                0x01 => Self::SelectPlanes { planes: x },
+                0x02 if x == 0 => Self::LoadPattern,
+                0x07 | 0x0F => Self::LoadDelay { x },
+                0x0A => Self::WaitKey { x },
+                0x15 => Self::SetDelay { x },
+                0x18 => Self::SetSound { x },
+                0x1E => Self::AddI { x },
+                0x29 => Self::LoadFont { x },
+                0x30 => Self::LoadHiResFont { x },
+                0x33 => Self::StoreBcd { x },
+                0x3A => Self::SetPitch { x },
+                0x55 => Self::StoreRegisters { x },
+                0x65 => Self::LoadRegisters { x },
+                0x75 => Self::StoreFlags { x },
+                0x85 => Self::LoadFlags { x },
+                _ => return Err(CpuError::InvalidOpcode(opcode)),
+            },
+            _ => return Err(CpuError::InvalidOpcode(opcode)),
+        };
+
+        Ok(instruction)
+    }
+}
+
+/// Formats the instruction in the usual assembler notation, e.g. `LD V1, 0x2A`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::MachineCall { address } => write!(f, "SYS {:#05X}", address),
+            Self::Clear => write!(f, "CLS"),
+            Self::Return => write!(f, "RET"),
+            Self::Exit => write!(f, "EXIT"),
+            Self::LoRes => write!(f, "LOW"),
+            Self::HiRes => write!(f, "HIGH"),
+            Self::Jump { address } => write!(f, "JP {:#05X}", address),
+            Self::Call { address } => write!(f, "CALL {:#05X}", address),
+            Self::SkipEqualByte { x, byte } => write!(f, "SE V{:X}, {:#04X}", x, byte),
+            Self::SkipNotEqualByte { x, byte } => write!(f, "SNE V{:X}, {:#04X}", x, byte),
+            Self::SkipEqual { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Self::SaveRange { x, y } => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            Self::LoadRange { x, y } => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            Self::LoadByte { x, byte } => write!(f, "LD V{:X}, {:#04X}", x, byte),
+            Self::AddByte { x, byte } => write!(f, "ADD V{:X}, {:#04X}", x, byte),
+            Self::Load { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Self::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Self::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Self::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Self::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Self::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Self::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Self::SubN { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Self::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Self::SkipNotEqual { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Self::LoadI { address } => write!(f, "LD I, {:#05X}", address),
+            Self::JumpOffset { address, .. } => write!(f, "JP V0, {:#05X}", address),
+            Self::Random { x, byte } => write!(f, "RND V{:X}, {:#04X}", x, byte),
+            Self::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Self::SkipKeyPressed { x } => write!(f, "SKP V{:X}", x),
+            Self::SkipKeyNotPressed { x } => write!(f, "SKNP V{:X}", x),
+            Self::SelectPlanes { planes } => write!(f, "PLANE {}", planes),
+            Self::LoadPattern => write!(f, "AUDIO"),
+            Self::LoadDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Self::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Self::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Self::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Self::AddI { x } => write!(f, "ADD I, V{:X}", x),
+            Self::LoadFont { x } => write!(f, "LD F, V{:X}", x),
+            Self::LoadHiResFont { x } => write!(f, "LD HF, V{:X}", x),
+            Self::StoreBcd { x } => write!(f, "LD B, V{:X}", x),
+            Self::SetPitch { x } => write!(f, "PITCH V{:X}", x),
+            Self::StoreRegisters { x } => write!(f, "LD [I], V{:X}", x),
+            Self::LoadRegisters { x } => write!(f, "LD V{:X}, [I]", x),
+            Self::StoreFlags { x } => write!(f, "LD R, V{:X}", x),
+            Self::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod instruction_tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(Instruction::decode(0x00E0), Ok(Instruction::Clear));
+        assert_eq!(
+            Instruction::decode(0x0123),
+            Ok(Instruction::MachineCall { address: 0x123 })
+        );
+        assert_eq!(
+            Instruction::decode(0x6A2B),
+            Ok(Instruction::LoadByte { x: 0xA, byte: 0x2B })
+        );
+        assert_eq!(
+            Instruction::decode(0xD125),
+            Ok(Instruction::Draw { x: 1, y: 2, n: 5 })
+        );
+        assert_eq!(
+            Instruction::decode(0xF30F),
+            Ok(Instruction::LoadDelay { x: 3 })
+        );
+
+        for opcode in [0x5121, 0x8128, 0x9121, 0xE1A2, 0xF102, 0xF1FF] {
+            assert_eq!(
+                Instruction::decode(opcode),
+                Err(CpuError::InvalidOpcode(opcode))
+            );
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let lines: Vec<String> = [0x00EE, 0x1204, 0x6A2B, 0x8126, 0xA050, 0xD125, 0xF565]
+            .into_iter()
+            .map(|opcode| Instruction::decode(opcode).unwrap().to_string())
+            .collect();
+
+        assert_eq!(
+            lines,
+            [
+                "RET",
+                "JP 0x204",
+                "LD VA, 0x2B",
+                "SHR V1, V2",
+                "LD I, 0x050",
+                "DRW V1, V2, 5",
+                "LD V5, [I]",
+            ]
+        );
+    }
+}
@@ -24,11 +24,13 @@ pub trait Write {
     ) -> Result<(), MemoryError>;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MemoryError {
     OutOfBounds,
     InvalidRange,
     DoesNotExist,
     StackOverflow,
     StackUnderflow,
+    /// The address lies in a region programs may not write to.
+    Protected,
 }
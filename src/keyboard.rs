@@ -1,60 +1,264 @@
-use std::{
-    sync::{Condvar, Mutex},
-    thread,
-    time::Duration,
+use std::sync::{
+    atomic::{AtomicU16, Ordering},
+    Condvar, Mutex,
 };
 
-use log::trace;
+use log::{trace, warn};
 
+/// State of the 16-key hex keypad, shared between the CPU and the input thread.
+///
+/// The pressed keys live in a lock-free bitmask, bit `n` being key `n`. The mutex guards no
+/// data and only pairs with the condvar to wake up `wait_for_key`, so poisoning can never
+/// leave the key state half-written.
 #[derive(Debug)]
 pub struct Keyboard {
-    pressed_key: Mutex<u8>,
+    pressed_keys: AtomicU16,
+    key_pressed_lock: Mutex<()>,
     key_pressed_cv: Condvar,
 }
 impl Keyboard {
     pub fn new() -> Self {
         Self {
-            pressed_key: Mutex::new(0x0),
+            pressed_keys: AtomicU16::new(0),
+            key_pressed_lock: Mutex::new(()),
             key_pressed_cv: Condvar::new(),
         }
     }
 
+    /// Marks `key` as pressed. Keys above 0xF are ignored.
     pub fn set_key(&self, key: u8) {
-        let mut pressed_key_lock = self.pressed_key.lock().unwrap_or_else(|p| p.into_inner());
-        *pressed_key_lock = key;
+        let Some(mask) = key_mask(key) else {
+            warn!("Ignoring press of invalid key {}", key);
+            return;
+        };
 
-        trace!("Set pressed key to {}", *pressed_key_lock);
+        self.pressed_keys.fetch_or(mask, Ordering::SeqCst);
 
+        trace!("Set key {} pressed", key);
+
+        // Take the lock so a waiter can not miss the notification between its check and wait.
+        let _lock = self
+            .key_pressed_lock
+            .lock()
+            .unwrap_or_else(|p| p.into_inner());
         self.key_pressed_cv.notify_all();
     }
 
-    pub fn release_key(&self) {
-        let mut pressed_key_lock = self.pressed_key.lock().unwrap_or_else(|p| p.into_inner());
-        *pressed_key_lock = 0x0;
+    /// Marks `key` as released. Keys above 0xF are ignored.
+    pub fn release_key(&self, key: u8) {
+        let Some(mask) = key_mask(key) else {
+            warn!("Ignoring release of invalid key {}", key);
+            return;
+        };
+
+        self.pressed_keys.fetch_and(!mask, Ordering::SeqCst);
 
-        trace!("Released button");
+        trace!("Released key {}", key);
     }
 
     pub fn is_key_pressed(&self, key: u8) -> bool {
         trace!("Check if key is pressed");
 
-        let pressed_key_lock = self.pressed_key.lock().unwrap_or_else(|p| p.into_inner());
-        *pressed_key_lock == key
+        key_mask(key).is_some_and(|mask| self.pressed_keys.load(Ordering::SeqCst) & mask != 0)
+    }
+
+    /// The raw keypad state, bit `n` set meaning key `n` is pressed.
+    pub fn state(&self) -> u16 {
+        self.pressed_keys.load(Ordering::SeqCst)
     }
 
-    /// Blocks the thread until the key is pressed.
+    /// Replaces the whole keypad state at once, e.g. for input received over the network.
+    /// Bit `n` set means key `n` is pressed.
+    pub fn set_state(&self, mask: u16) {
+        self.pressed_keys.store(mask, Ordering::SeqCst);
+
+        trace!("Set keyboard state to {:#06X}", mask);
+
+        if mask != 0 {
+            let _lock = self
+                .key_pressed_lock
+                .lock()
+                .unwrap_or_else(|p| p.into_inner());
+            self.key_pressed_cv.notify_all();
+        };
+    }
+
+    pub fn is_any_key_pressed(&self) -> bool {
+        self.pressed_keys.load(Ordering::SeqCst) != 0
+    }
+
+    /// Iterates over the keys held at the time of the call, lowest first.
+    pub fn pressed_keys(&self) -> impl Iterator<Item = u8> {
+        let pressed_keys = self.pressed_keys.load(Ordering::SeqCst);
+
+        (0..=0xF).filter(move |&key| pressed_keys & (1 << key) != 0)
+    }
+
+    /// Blocks the thread until a key is pressed and returns the lowest pressed key.
     pub fn wait_for_key(&self) -> u8 {
         trace!("Waiting for key press");
-        let mut pressed_key_lock = self.pressed_key.lock().unwrap_or_else(|p| p.into_inner());
+        let mut lock = self
+            .key_pressed_lock
+            .lock()
+            .unwrap_or_else(|p| p.into_inner());
+
+        loop {
+            if let Some(key) = self.pressed_keys().next() {
+                trace!("Received key");
+                return key;
+            };
 
-        while *pressed_key_lock == 0x0 {
-            pressed_key_lock = self
+            lock = self
                 .key_pressed_cv
-                .wait(pressed_key_lock)
+                .wait(lock)
                 .unwrap_or_else(|p| p.into_inner());
         }
+    }
+}
+
+fn key_mask(key: u8) -> Option<u16> {
+    (key <= 0xF).then(|| 1 << key)
+}
+
+#[cfg(test)]
+mod keyboard_tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn test_set_release_key() {
+        let keyboard = Keyboard::new();
+        assert!(!keyboard.is_key_pressed(0x0));
+
+        keyboard.set_key(0x0);
+        keyboard.set_key(0xF);
+        assert!(keyboard.is_key_pressed(0x0));
+        assert!(keyboard.is_key_pressed(0xF));
+        assert!(!keyboard.is_key_pressed(0x5));
+
+        keyboard.release_key(0x0);
+        assert!(!keyboard.is_key_pressed(0x0));
+        assert!(keyboard.is_key_pressed(0xF));
+
+        keyboard.set_key(0x10);
+        assert!(!keyboard.is_key_pressed(0x10));
+    }
+
+    #[test]
+    fn test_invalid_key_keeps_state() {
+        let keyboard = Keyboard::new();
+        keyboard.set_key(0x4);
+
+        // A key without a CHIP-8 mapping neither presses nor releases anything.
+        for key in [0x10, 0xFF] {
+            keyboard.set_key(key);
+            keyboard.release_key(key);
+        }
+
+        assert_eq!(keyboard.state(), 1 << 0x4);
+        assert_eq!(keyboard.pressed_keys().collect::<Vec<u8>>(), [0x4]);
+    }
+
+    #[test]
+    fn test_pressed_keys() {
+        let keyboard = Keyboard::new();
+        assert!(!keyboard.is_any_key_pressed());
+        assert_eq!(keyboard.pressed_keys().count(), 0);
+
+        keyboard.set_key(0x7);
+        assert!(keyboard.is_any_key_pressed());
+        assert_eq!(keyboard.pressed_keys().collect::<Vec<u8>>(), [0x7]);
+
+        keyboard.set_key(0xF);
+        keyboard.set_key(0x0);
+        assert_eq!(
+            keyboard.pressed_keys().collect::<Vec<u8>>(),
+            [0x0, 0x7, 0xF]
+        );
+
+        for key in [0x0, 0x7, 0xF] {
+            keyboard.release_key(key);
+        }
+        assert!(!keyboard.is_any_key_pressed());
+    }
+
+    #[test]
+    fn test_state() {
+        let keyboard = Keyboard::new();
+        assert_eq!(keyboard.state(), 0);
+
+        keyboard.set_state(0b1000_0000_0010_0001);
+        for key in 0..16 {
+            assert_eq!(keyboard.is_key_pressed(key), matches!(key, 0x0 | 0x5 | 0xF));
+        }
+
+        keyboard.set_key(0x1);
+        keyboard.release_key(0xF);
+        assert_eq!(keyboard.state(), 0b0000_0000_0010_0011);
+
+        keyboard.set_state(0);
+        assert!(!keyboard.is_any_key_pressed());
+    }
+
+    #[test]
+    fn test_set_state_wakes_waiter() {
+        let keyboard = Arc::new(Keyboard::new());
+
+        let waiter = {
+            let keyboard = keyboard.clone();
+            thread::spawn(move || keyboard.wait_for_key())
+        };
+
+        thread::sleep(Duration::from_millis(20));
+        keyboard.set_state(1 << 0xA);
+
+        assert_eq!(waiter.join().unwrap(), 0xA);
+    }
+
+    #[test]
+    fn test_concurrent_set_release() {
+        let keyboard = Arc::new(Keyboard::new());
+
+        let handles: Vec<_> = (0..16u8)
+            .map(|key| {
+                let keyboard = keyboard.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        keyboard.set_key(key);
+                        assert!(keyboard.is_key_pressed(key));
+                        keyboard.release_key(key);
+                    }
+
+                    // Leave every odd key pressed.
+                    if key % 2 == 1 {
+                        keyboard.set_key(key);
+                    };
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        for key in 0..16 {
+            assert_eq!(keyboard.is_key_pressed(key), key % 2 == 1);
+        }
+    }
+
+    #[test]
+    fn test_wait_for_key() {
+        let keyboard = Arc::new(Keyboard::new());
+
+        let waiter = {
+            let keyboard = keyboard.clone();
+            thread::spawn(move || keyboard.wait_for_key())
+        };
+
+        thread::sleep(Duration::from_millis(20));
+        keyboard.set_key(0x0);
 
-        trace!("Received key");
-        *pressed_key_lock
+        assert_eq!(waiter.join().unwrap(), 0x0);
     }
 }
@@ -0,0 +1,25 @@
+#![allow(
+    clippy::upper_case_acronyms,
+    clippy::expect_fun_call,
+    clippy::new_without_default
+)]
+
+pub mod builder;
+pub mod cpu;
+pub mod inspector;
+pub mod instruction;
+pub mod io;
+pub mod keyboard;
+pub mod overlay;
+pub mod quirks;
+pub mod ram;
+pub mod registers;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod renderer;
+pub mod rewind;
+pub mod rom;
+pub mod screen;
+pub mod sound;
+pub mod timer;
+pub mod undo;
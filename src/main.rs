@@ -1,33 +1,113 @@
-use std::{env, fs};
+use std::{
+    env,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
 
-use cpu::CPU;
+use chip_8_emulator::{cpu::CPU, quirks::Quirks, rom};
 use log::error;
 
-mod cpu;
-mod io;
-mod keyboard;
-mod ram;
-mod registers;
-mod screen;
-mod timer;
-
 fn main() {
     env_logger::init();
 
     let args: Vec<String> = env::args().collect();
 
     if args.len() < 2 {
-        eprintln!("Usage: {} <program_path>", args[0]);
+        eprintln!(
+            "Usage: {} [--quirks <chip8|schip|xo-chip|quirks.toml>] <program_path|rom_directory>",
+            args[0]
+        );
         error!("No arguments given!");
         std::process::exit(1);
     };
 
-    let program_path: String = args[args.len() - 1].clone();
-
-    let program = fs::read(program_path).expect("Failed to read program!");
+    let mut program_path = PathBuf::from(&args[args.len() - 1]);
+    if program_path.is_dir() {
+        program_path = choose_rom(&program_path);
+    };
 
     let mut cpu = CPU::new();
-    cpu.load_rom(&program)
+
+    if let Some(position) = args.iter().position(|arg| arg == "--quirks") {
+        let name = args.get(position + 1).map(String::as_str).unwrap_or("");
+
+        cpu.set_quirks(parse_quirks(name));
+    };
+    cpu.load_rom_from_path(program_path)
         .expect("Could not load ROM into RAM!");
-    cpu.clock();
+
+    // The CPU already logs why it halted.
+    if cpu.clock().is_err() {
+        std::process::exit(1);
+    };
+}
+
+/// Looks up a quirk preset or, for a `.toml` path, loads the quirks from that file.
+fn parse_quirks(name: &str) -> Quirks {
+    let path = Path::new(name);
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "toml")
+    {
+        #[cfg(feature = "config")]
+        match Quirks::load(path) {
+            Ok(quirks) => return quirks,
+            Err(error) => {
+                eprintln!("Could not load quirks from {}: {:?}", name, error);
+                std::process::exit(1);
+            }
+        };
+
+        #[cfg(not(feature = "config"))]
+        {
+            eprintln!("Loading quirks from a file needs the `config` feature.");
+            std::process::exit(1);
+        };
+    };
+
+    match Quirks::from_name(name) {
+        Some(quirks) => quirks,
+        None => {
+            eprintln!("Unknown quirk preset: {}", name);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Lists the ROMs in `dir` and asks on stdin which one to run.
+fn choose_rom(dir: &Path) -> PathBuf {
+    let roms = rom::list_roms(dir).expect("Could not read ROM directory!");
+
+    if roms.is_empty() {
+        eprintln!("No .ch8 files in {}", dir.display());
+        std::process::exit(1);
+    };
+
+    for (index, path) in roms.iter().enumerate() {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        println!("[{}] {}", index + 1, name);
+    }
+
+    let stdin = io::stdin();
+    loop {
+        print!("Select a ROM: ");
+        io::stdout().flush().expect("Could not flush stdout!");
+
+        let mut line = String::new();
+        if stdin
+            .lock()
+            .read_line(&mut line)
+            .expect("Could not read stdin!")
+            == 0
+        {
+            std::process::exit(1);
+        };
+
+        match line.trim().parse::<usize>() {
+            Ok(number) if (1..=roms.len()).contains(&number) => {
+                return roms[number - 1].clone();
+            }
+            _ => eprintln!("Enter a number from 1 to {}", roms.len()),
+        };
+    }
 }
@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use log::{info, trace};
+
+use crate::cpu::{CpuStats, CPU};
+
+/// How long FPS and IPS are averaged over before the shown values update.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// State of a debug overlay showing frames per second, instructions per second, PC and I.
+/// Hidden by default, the front-end toggles it (e.g. with F3), reports every presented frame
+/// through `frame` and draws the `lines` on top of the screen.
+#[derive(Debug)]
+pub struct DebugOverlay {
+    visible: bool,
+
+    // Counted since the start of the current sample.
+    frames: u32,
+    elapsed: Duration,
+    // Executed instructions when the current sample started.
+    sample_start_instructions: Option<u64>,
+
+    fps: f64,
+    ips: f64,
+}
+impl DebugOverlay {
+    pub fn new() -> Self {
+        DebugOverlay {
+            visible: false,
+            frames: 0,
+            elapsed: Duration::ZERO,
+            sample_start_instructions: None,
+            fps: 0.,
+            ips: 0.,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        info!("Setting debug overlay visibility to {}", self.visible);
+    }
+
+    /// Counts a frame that took `delta`. `stats` are the CPU's stats after the frame, whose
+    /// `instruction_count` growth gives the IPS. The averages update every `SAMPLE_INTERVAL`.
+    pub fn frame(&mut self, stats: &CpuStats, delta: Duration) {
+        let start = *self
+            .sample_start_instructions
+            .get_or_insert(stats.instruction_count);
+
+        self.frames += 1;
+        self.elapsed += delta;
+
+        if self.elapsed < SAMPLE_INTERVAL {
+            return;
+        };
+
+        let seconds = self.elapsed.as_secs_f64();
+        self.fps = self.frames as f64 / seconds;
+        // The counter drops back to 0 when the stats get reset.
+        self.ips = stats.instruction_count.saturating_sub(start) as f64 / seconds;
+        trace!("Overlay sample: {:.1} FPS, {:.0} IPS", self.fps, self.ips);
+
+        self.frames = 0;
+        self.elapsed = Duration::ZERO;
+        self.sample_start_instructions = Some(stats.instruction_count);
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    pub fn ips(&self) -> f64 {
+        self.ips
+    }
+
+    /// The text to show, one entry per line.
+    pub fn lines(&self, cpu: &CPU) -> Vec<String> {
+        vec![
+            format!("FPS: {:.1}", self.fps),
+            format!("IPS: {:.0}", self.ips),
+            format!("PC:  {:04X}", cpu.program_counter()),
+            format!("I:   {:04X}", cpu.index_register().read()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod overlay_tests {
+    use super::*;
+
+    fn stats(instruction_count: u64) -> CpuStats {
+        CpuStats {
+            instruction_count,
+            ..CpuStats::default()
+        }
+    }
+
+    #[test]
+    fn test_fps_and_ips() {
+        let mut overlay = DebugOverlay::new();
+        let frame = Duration::from_millis(20);
+
+        // 25 frames of 20 ms are one sample at 50 FPS. The first frame only sets the baseline,
+        // the other 24 execute 10 instructions each.
+        for count in 0..25 {
+            assert_eq!(overlay.ips(), 0.);
+            overlay.frame(&stats(1000 + count * 10), frame);
+        }
+
+        assert!((overlay.fps() - 50.).abs() < 1e-6);
+        assert!((overlay.ips() - 480.).abs() < 1e-6);
+
+        // Every 500 ms frame is a sample of its own, 500 instructions in each.
+        overlay.frame(&stats(1740), Duration::from_millis(500));
+        overlay.frame(&stats(2240), Duration::from_millis(500));
+        assert!((overlay.fps() - 2.).abs() < 1e-6);
+        assert!((overlay.ips() - 1000.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut overlay = DebugOverlay::new();
+        assert!(!overlay.is_visible());
+        overlay.toggle();
+        assert!(overlay.is_visible());
+
+        let mut cpu = CPU::new();
+        cpu.index_register_mut().write(0x300);
+
+        assert_eq!(
+            overlay.lines(&cpu),
+            ["FPS: 0.0", "IPS: 0", "PC:  0200", "I:   0300"]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+#[cfg(feature = "config")]
+use std::path::Path;
+
+use log::trace;
+
+#[cfg(feature = "config")]
+#[derive(Debug)]
+pub enum QuirksError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+}
+
+#[cfg(feature = "config")]
+impl From<std::io::Error> for QuirksError {
+    fn from(error: std::io::Error) -> Self {
+        QuirksError::Io(error)
+    }
+}
+
+#[cfg(feature = "config")]
+impl From<toml::de::Error> for QuirksError {
+    fn from(error: toml::de::Error) -> Self {
+        QuirksError::Parse(error)
+    }
+}
+
+#[cfg(feature = "config")]
+impl From<toml::ser::Error> for QuirksError {
+    fn from(error: toml::ser::Error) -> Self {
+        QuirksError::Serialize(error)
+    }
+}
+
+/// Behaviors that differ between CHIP-8 interpreters.
+///
+/// `Default` keeps the behavior described in Cowgod's technical reference, which this emulator
+/// followed before quirks were configurable.
+///
+/// With the `config` feature the quirks can be stored as TOML, one `name = true|false` line per
+/// field. Missing fields take their `Default` value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct Quirks {
+    /// 8xy1, 8xy2 and 8xy3 set VF to 0.
+    pub vf_reset: bool,
+    /// 8xy6 and 8xyE shift V(y) into V(x) instead of shifting V(x) in place.
+    pub shift_uses_vy: bool,
+    /// Bnnn jumps to nnn + V(x) instead of nnn + V(0).
+    pub jump_uses_vx: bool,
+    /// Fx55 and Fx65 leave I at I + x + 1 instead of unchanged.
+    pub increment_i_on_load_store: bool,
+    /// Dxyn waits for the vertical blank, so at most one sprite is drawn per 60 Hz frame.
+    pub display_wait: bool,
+    /// Fx1E sets VF to 1 if I + V(x) leaves the 12-bit address space and to 0 otherwise, like the
+    /// Amiga interpreter. Spacefight 2091! relies on it.
+    pub fx1e_overflow_flag: bool,
+    /// Dxyn cuts sprites off at the right and bottom edges instead of wrapping them around.
+    pub clip_sprites: bool,
+    /// Enables the XO-CHIP instructions: 5xy2 and 5xy3 store and load the registers V(x)
+    /// through V(y) at I, Fn01 selects the drawing planes, F002 loads an audio pattern from I
+    /// and Fx3A sets its pitch.
+    pub xo_chip_instructions: bool,
+    /// Dxy0 is an invalid opcode instead of drawing nothing. SCHIP uses it for 16x16 sprites,
+    /// which are not supported, so this is a way to catch programs that need them.
+    pub zero_height_sprite_invalid: bool,
+}
+impl Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            vf_reset: true,
+            shift_uses_vy: true,
+            jump_uses_vx: false,
+            increment_i_on_load_store: true,
+            display_wait: true,
+            fx1e_overflow_flag: false,
+            clip_sprites: true,
+            xo_chip_instructions: false,
+            zero_height_sprite_invalid: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1 as found on the HP48.
+    pub fn super_chip() -> Self {
+        Quirks {
+            vf_reset: false,
+            shift_uses_vy: false,
+            jump_uses_vx: true,
+            increment_i_on_load_store: false,
+            display_wait: false,
+            fx1e_overflow_flag: false,
+            clip_sprites: true,
+            xo_chip_instructions: false,
+            zero_height_sprite_invalid: false,
+        }
+    }
+
+    /// XO-CHIP as implemented by Octo.
+    pub fn xo_chip() -> Self {
+        Quirks {
+            vf_reset: false,
+            shift_uses_vy: true,
+            jump_uses_vx: false,
+            increment_i_on_load_store: true,
+            display_wait: false,
+            fx1e_overflow_flag: false,
+            clip_sprites: false,
+            xo_chip_instructions: true,
+            zero_height_sprite_invalid: false,
+        }
+    }
+
+    /// Looks up a preset by name, ignoring case, `-` and `_`.
+    /// Knows `chip8`/`vip`/`cosmacvip`, `schip`/`superchip` and `xochip`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name: String = name
+            .chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_lowercase();
+
+        trace!("Looking up quirk preset {}", name);
+
+        match name.as_str() {
+            "chip8" | "vip" | "cosmacvip" => Some(Quirks::cosmac_vip()),
+            "schip" | "superchip" => Some(Quirks::super_chip()),
+            "xochip" => Some(Quirks::xo_chip()),
+            _ => None,
+        }
+    }
+
+    /// Reads quirks from a TOML file, e.g. to keep per-ROM settings next to the ROM.
+    #[cfg(feature = "config")]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, QuirksError> {
+        trace!("Loading quirks from {}", path.as_ref().display());
+
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Writes the quirks to a TOML file that `load` reads back.
+    #[cfg(feature = "config")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), QuirksError> {
+        trace!("Saving quirks to {}", path.as_ref().display());
+
+        std::fs::write(path, toml::to_string(self)?)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod quirks_tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        let vip = Quirks::cosmac_vip();
+        assert!(vip.vf_reset);
+        assert!(vip.shift_uses_vy);
+        assert!(!vip.jump_uses_vx);
+        assert!(vip.increment_i_on_load_store);
+        assert!(vip.display_wait);
+        assert!(!vip.fx1e_overflow_flag);
+        assert!(vip.clip_sprites);
+        assert!(!vip.xo_chip_instructions);
+        assert!(!vip.zero_height_sprite_invalid);
+
+        let schip = Quirks::super_chip();
+        assert!(!schip.vf_reset);
+        assert!(!schip.shift_uses_vy);
+        assert!(schip.jump_uses_vx);
+        assert!(!schip.increment_i_on_load_store);
+        assert!(!schip.display_wait);
+        assert!(!schip.fx1e_overflow_flag);
+        assert!(schip.clip_sprites);
+        assert!(!schip.xo_chip_instructions);
+        assert!(!schip.zero_height_sprite_invalid);
+
+        let xo_chip = Quirks::xo_chip();
+        assert!(!xo_chip.vf_reset);
+        assert!(xo_chip.shift_uses_vy);
+        assert!(!xo_chip.jump_uses_vx);
+        assert!(xo_chip.increment_i_on_load_store);
+        assert!(!xo_chip.display_wait);
+        assert!(!xo_chip.fx1e_overflow_flag);
+        assert!(!xo_chip.clip_sprites);
+        assert!(xo_chip.xo_chip_instructions);
+        assert!(!xo_chip.zero_height_sprite_invalid);
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Quirks::from_name("chip8"), Some(Quirks::cosmac_vip()));
+        assert_eq!(Quirks::from_name("COSMAC-VIP"), Some(Quirks::cosmac_vip()));
+        assert_eq!(Quirks::from_name("schip"), Some(Quirks::super_chip()));
+        assert_eq!(Quirks::from_name("Super_Chip"), Some(Quirks::super_chip()));
+        assert_eq!(Quirks::from_name("xo-chip"), Some(Quirks::xo_chip()));
+        assert_eq!(Quirks::from_name("megachip"), None);
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_save_load() {
+        let path = std::env::temp_dir().join(format!("chip_8_quirks_{}.toml", std::process::id()));
+        let quirks = Quirks {
+            display_wait: false,
+            zero_height_sprite_invalid: true,
+            ..Quirks::cosmac_vip()
+        };
+
+        let saved = quirks.save(&path);
+        let loaded = Quirks::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        saved.unwrap();
+        assert_eq!(loaded.unwrap(), quirks);
+
+        let partial: Quirks = toml::from_str("clip_sprites = true").unwrap();
+        assert_eq!(
+            partial,
+            Quirks {
+                clip_sprites: true,
+                ..Quirks::default()
+            }
+        );
+
+        assert!(matches!(
+            Quirks::load(std::env::temp_dir().join("chip_8_quirks_does_not_exist.toml")),
+            Err(QuirksError::Io(_))
+        ));
+    }
+}
@@ -1,15 +1,28 @@
 use crate::io::{self, MemoryError};
 
-#[derive(Debug)]
+/// Size of the addressable memory in bytes.
+pub const RAM_SIZE: usize = 0x1000;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct RAM {
-    memory: [u8; 0x1000],
+    memory: [u8; RAM_SIZE],
 }
 impl RAM {
     pub fn new() -> Self {
         RAM {
-            memory: [0u8; 0x1000],
+            memory: [0u8; RAM_SIZE],
         }
     }
+
+    /// Reads the 16-bit value at `address`. CHIP-8 is big-endian, so the byte at `address` is
+    /// the high byte. Fails if the second byte lies past the end of RAM.
+    pub fn read_u16(&self, address: u16) -> Result<u16, MemoryError> {
+        let bytes = self.memory.get(address as usize..address as usize + 2);
+
+        bytes
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .ok_or(MemoryError::OutOfBounds)
+    }
 }
 
 impl io::Read for RAM {
@@ -27,7 +40,7 @@ impl io::Read for RAM {
         if start_address
             .checked_add(end_offset)
             .ok_or(MemoryError::InvalidRange)?
-            >= self.memory.len() as u16
+            > self.memory.len() as u16
         {
             return Err(MemoryError::OutOfBounds);
         };
@@ -61,7 +74,7 @@ impl io::Write for RAM {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Stack {
     stack_pointer: u8,
     stack: [u16; 16],
@@ -101,4 +114,106 @@ impl Stack {
             .cloned()
             .ok_or(MemoryError::DoesNotExist)
     }
+
+    /// Returns the top element without removing it, or None if the stack is empty.
+    pub fn peek(&self) -> Option<u16> {
+        self.frames().last().copied()
+    }
+
+    /// Number of return addresses currently on the stack.
+    pub fn depth(&self) -> usize {
+        self.stack_pointer as usize
+    }
+
+    /// Number of return addresses the stack can hold.
+    pub fn capacity(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// The pushed return addresses, oldest first.
+    pub fn frames(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer as usize]
+    }
+}
+
+#[cfg(test)]
+mod ram_tests {
+    use super::*;
+    use crate::io::{Read, Write};
+
+    #[test]
+    fn test_read_range_up_to_end() {
+        let mut ram = RAM::new();
+        ram.write_buf(RAM_SIZE as u16 - 2, &[0xAB, 0xCD]).unwrap();
+
+        assert_eq!(
+            ram.read_range(RAM_SIZE as u16 - 2, 2).unwrap(),
+            &[0xAB, 0xCD]
+        );
+        assert_eq!(ram.read_range(RAM_SIZE as u16, 0).unwrap(), &[]);
+        assert_eq!(
+            ram.read_range(RAM_SIZE as u16 - 2, 3),
+            Err(MemoryError::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn test_read_u16() {
+        let mut ram = RAM::new();
+        ram.write_buf(0x200, &[0x12, 0x34]).unwrap();
+        ram.write_buf(RAM_SIZE as u16 - 2, &[0xAB, 0xCD]).unwrap();
+
+        assert_eq!(ram.read_u16(0x200), Ok(0x1234));
+        assert_eq!(ram.read_u16(0x201), Ok(0x3400));
+        assert_eq!(ram.read_u16(RAM_SIZE as u16 - 2), Ok(0xABCD));
+        assert_eq!(
+            ram.read_u16(RAM_SIZE as u16 - 1),
+            Err(MemoryError::OutOfBounds)
+        );
+        assert_eq!(ram.read_u16(u16::MAX), Err(MemoryError::OutOfBounds));
+    }
+
+    #[test]
+    fn test_stack_depth() {
+        let mut stack = Stack::new();
+        assert_eq!(stack.depth(), 0);
+        assert_eq!(stack.capacity(), 16);
+
+        for depth in 1..=3 {
+            stack.push(0x200 + depth as u16 * 2).unwrap();
+            assert_eq!(stack.depth(), depth);
+        }
+
+        assert_eq!(stack.pop().unwrap(), 0x206);
+        assert_eq!(stack.depth(), 2);
+        assert_eq!(stack.frames(), &[0x202, 0x204]);
+
+        while stack.depth() < stack.capacity() {
+            stack.push(0x300).unwrap();
+        }
+        assert_eq!(stack.push(0x300), Err(MemoryError::StackOverflow));
+        assert_eq!(stack.depth(), stack.capacity());
+
+        while stack.depth() > 0 {
+            stack.pop().unwrap();
+        }
+        assert_eq!(stack.pop(), Err(MemoryError::StackUnderflow));
+        assert_eq!(stack.depth(), 0);
+    }
+
+    #[test]
+    fn test_stack_peek() {
+        let mut stack = Stack::new();
+        assert_eq!(stack.peek(), None);
+        assert!(stack.frames().is_empty());
+
+        stack.push(0x202).unwrap();
+        stack.push(0x2A4).unwrap();
+        assert_eq!(stack.frames(), &[0x202, 0x2A4]);
+
+        while let Some(top) = stack.peek() {
+            assert_eq!(stack.pop(), Ok(top));
+        }
+        assert_eq!(stack.depth(), 0);
+    }
 }
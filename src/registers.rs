@@ -1,6 +1,64 @@
 use crate::io::{self, MemoryError};
 
-#[derive(Debug)]
+/// Names of the 16 general purpose registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Register {
+    V0,
+    V1,
+    V2,
+    V3,
+    V4,
+    V5,
+    V6,
+    V7,
+    V8,
+    V9,
+    VA,
+    VB,
+    VC,
+    VD,
+    VE,
+    VF,
+}
+impl Register {
+    pub const ALL: [Register; 16] = [
+        Register::V0,
+        Register::V1,
+        Register::V2,
+        Register::V3,
+        Register::V4,
+        Register::V5,
+        Register::V6,
+        Register::V7,
+        Register::V8,
+        Register::V9,
+        Register::VA,
+        Register::VB,
+        Register::VC,
+        Register::VD,
+        Register::VE,
+        Register::VF,
+    ];
+}
+
+impl From<Register> for u8 {
+    fn from(register: Register) -> Self {
+        register as u8
+    }
+}
+
+impl TryFrom<u8> for Register {
+    type Error = MemoryError;
+
+    fn try_from(index: u8) -> Result<Self, MemoryError> {
+        Register::ALL
+            .get(index as usize)
+            .copied()
+            .ok_or(MemoryError::OutOfBounds)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct V {
     v: [u8; 16],
 }
@@ -8,6 +66,19 @@ impl V {
     pub fn new() -> Self {
         V { v: [0u8; 16] }
     }
+
+    pub fn get(&self, register: Register) -> u8 {
+        self.v[register as usize]
+    }
+
+    pub fn set(&mut self, register: Register, data: u8) {
+        self.v[register as usize] = data;
+    }
+
+    /// Copy of all 16 registers, V0 first.
+    pub fn all(&self) -> [u8; 16] {
+        self.v
+    }
 }
 
 impl io::Read for V {
@@ -25,7 +96,7 @@ impl io::Read for V {
         if start_address
             .checked_add(end_offset)
             .ok_or(MemoryError::InvalidRange)?
-            >= self.v.len() as u8
+            > self.v.len() as u8
         {
             return Err(MemoryError::OutOfBounds);
         };
@@ -48,8 +119,11 @@ impl io::Write for V {
     }
 
     fn write_buf(&mut self, start_address: u8, data: &[Self::Bit]) -> Result<(), MemoryError> {
-        let end_address = start_address
-            .checked_add(data.len() as u8)
+        // More than 255 bytes can never fit and would wrap in the cast.
+        let end_address = u8::try_from(data.len())
+            .ok()
+            .and_then(|length| start_address.checked_add(length))
+            .filter(|&end| end as usize <= self.v.len())
             .ok_or(MemoryError::OutOfBounds)?;
 
         self.v[start_address as usize..end_address as usize].copy_from_slice(data);
@@ -58,7 +132,7 @@ impl io::Write for V {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct I {
     i: u16,
 }
@@ -75,3 +149,80 @@ impl I {
         self.i = data;
     }
 }
+
+#[cfg(test)]
+mod registers_tests {
+    use super::*;
+    use crate::io::{Read, Write};
+
+    #[test]
+    fn test_register_conversion() {
+        for (index, register) in Register::ALL.iter().enumerate() {
+            assert_eq!(u8::from(*register), index as u8);
+            assert_eq!(Register::try_from(index as u8).unwrap(), *register);
+        }
+
+        assert_eq!(Register::try_from(16), Err(MemoryError::OutOfBounds));
+    }
+
+    #[test]
+    fn test_write_buf_bounds() {
+        let mut v = V::new();
+
+        v.write_buf(0, &[0xAA; 16]).unwrap();
+        v.write_buf(14, &[0x01, 0x02]).unwrap();
+        assert_eq!(v.get(Register::VE), 0x01);
+        assert_eq!(v.get(Register::VF), 0x02);
+
+        assert_eq!(v.write_buf(15, &[0, 0]), Err(MemoryError::OutOfBounds));
+        assert_eq!(v.write_buf(0, &[0; 17]), Err(MemoryError::OutOfBounds));
+        assert_eq!(v.write_buf(0, &[0; 256]), Err(MemoryError::OutOfBounds));
+        assert_eq!(v.write_buf(u8::MAX, &[0]), Err(MemoryError::OutOfBounds));
+
+        // Nothing was written by the failed calls.
+        assert_eq!(v.get(Register::V0), 0xAA);
+        assert_eq!(v.get(Register::VF), 0x02);
+    }
+
+    #[test]
+    fn test_read_range_bounds() {
+        let mut v = V::new();
+        v.write_buf(0, &[0x11; 16]).unwrap();
+
+        assert_eq!(v.read_range(0, 1).unwrap(), &[0x11]);
+        assert_eq!(v.read_range(0, 16).unwrap(), &[0x11; 16]);
+        assert_eq!(v.read_range(15, 1).unwrap(), &[0x11]);
+        assert_eq!(v.read_range(16, 0).unwrap(), &[]);
+        assert_eq!(v.read_range(15, 2), Err(MemoryError::OutOfBounds));
+        assert_eq!(v.read_range(u8::MAX, 1), Err(MemoryError::InvalidRange));
+    }
+
+    #[test]
+    fn test_all() {
+        let mut v = V::new();
+        for (index, register) in Register::ALL.iter().enumerate() {
+            v.set(*register, index as u8 * 3);
+        }
+
+        let all = v.all();
+        for (index, value) in all.iter().enumerate() {
+            assert_eq!(v.read(index as u8).unwrap(), *value);
+        }
+
+        // A copy, not a view.
+        v.set(Register::V0, 0xFF);
+        assert_eq!(all[0], 0);
+    }
+
+    #[test]
+    fn test_typed_access() {
+        let mut v = V::new();
+
+        v.set(Register::VA, 0x42);
+        assert_eq!(v.get(Register::VA), 0x42);
+        assert_eq!(v.read(0xA).unwrap(), 0x42);
+
+        v.write(0xF, 0x01).unwrap();
+        assert_eq!(v.get(Register::VF), 0x01);
+    }
+}
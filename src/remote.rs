@@ -0,0 +1,225 @@
+//! Line based TCP control server, e.g. for automated grading or embedding the emulator in a
+//! larger project.
+//!
+//! Every command is one line and answered with one line, `ok` optionally followed by a result,
+//! or `error <reason>`:
+//!
+//! - `step`: executes one instruction. Fx0A without a pressed key is retried by the next `step`.
+//! - `reset`: puts the machine back into its power-on state.
+//! - `load <base64>`: resets and loads the base64 encoded ROM at 0x200.
+//! - `keys <mask>`: sets the pressed keys, bit n being key n, as hex.
+//! - `screen`: answers with the width, the height and every pixel row by row as `0` or `1`.
+//! - `quit`: closes the connection.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{info, trace, warn};
+
+use crate::cpu::CPU;
+
+pub struct RemoteServer {
+    listener: TcpListener,
+}
+impl RemoteServer {
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        info!("Remote control listening on {}", listener.local_addr()?);
+
+        Ok(RemoteServer { listener })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Waits for a client and runs its commands against `cpu` until it sends `quit` or
+    /// disconnects. Fx0A does not block while serving, see `CPU::set_blocking_key_wait`, so
+    /// a `step` waiting for a key answers right away and the client can still send `keys`.
+    pub fn serve_one(&self, cpu: &mut CPU) -> io::Result<()> {
+        let (stream, peer) = self.listener.accept()?;
+        info!("Remote client {} connected.", peer);
+
+        let blocking_key_wait = cpu.blocking_key_wait();
+        cpu.set_blocking_key_wait(false);
+        let result = serve_stream(cpu, stream);
+        cpu.set_blocking_key_wait(blocking_key_wait);
+
+        info!("Remote client {} disconnected.", peer);
+
+        result
+    }
+}
+
+fn serve_stream(cpu: &mut CPU, stream: TcpStream) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim() == "quit" {
+            break;
+        };
+
+        writeln!(writer, "{}", handle_command(cpu, &line))?;
+    }
+
+    Ok(())
+}
+
+/// Runs a single command line against `cpu` and returns the answer without a line break.
+/// Unless blocking key waits are turned off, `step` on an Fx0A blocks until a key is pressed.
+pub fn handle_command(cpu: &mut CPU, line: &str) -> String {
+    trace!("Remote command: {}", line);
+
+    let (command, argument) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+
+    let result = match command {
+        "step" => cpu
+            .step()
+            .map(|_| String::new())
+            .map_err(|error| format!("{:?}", error)),
+        "reset" => {
+            cpu.reset();
+            Ok(String::new())
+        }
+        "load" => STANDARD
+            .decode(argument.trim())
+            .map_err(|error| error.to_string())
+            .and_then(|rom| cpu.reload_rom(&rom).map_err(|error| format!("{:?}", error)))
+            .map(|_| String::new()),
+        "keys" => u16::from_str_radix(argument.trim().trim_start_matches("0x"), 16)
+            .map_err(|error| error.to_string())
+            .map(|mask| {
+                cpu.keyboard().set_state(mask);
+                String::new()
+            }),
+        "screen" => {
+            let screen = cpu.screen();
+            let pixels: String = screen
+                .iter_pixels()
+                .map(|pixel| if pixel { '1' } else { '0' })
+                .collect();
+
+            Ok(format!("{} {} {}", screen.width(), screen.height(), pixels))
+        }
+        _ => Err(format!("unknown command {}", command)),
+    };
+
+    match result {
+        Ok(answer) if answer.is_empty() => "ok".to_string(),
+        Ok(answer) => format!("ok {}", answer),
+        Err(reason) => {
+            warn!("Remote command {} failed: {}", line, reason);
+            format!("error {}", reason)
+        }
+    }
+}
+
+#[cfg(test)]
+mod remote_tests {
+    use std::thread;
+
+    use super::*;
+    use crate::io::Read;
+
+    #[test]
+    fn test_handle_command() {
+        let mut cpu = CPU::new();
+
+        // V0 = 0x2A
+        assert_eq!(handle_command(&mut cpu, "load YCo="), "ok");
+        assert_eq!(handle_command(&mut cpu, "step"), "ok");
+        assert_eq!(cpu.registers().read(0).unwrap(), 0x2A);
+
+        assert_eq!(handle_command(&mut cpu, "keys 0x8001"), "ok");
+        assert_eq!(cpu.keyboard().state(), 0x8001);
+
+        assert_eq!(handle_command(&mut cpu, "reset"), "ok");
+        assert_eq!(cpu.registers().read(0).unwrap(), 0);
+
+        assert!(handle_command(&mut cpu, "load !!!").starts_with("error"));
+        assert!(handle_command(&mut cpu, "keys xyz").starts_with("error"));
+        assert_eq!(
+            handle_command(&mut cpu, "jump"),
+            "error unknown command jump"
+        );
+    }
+
+    #[test]
+    fn test_server_over_loopback() {
+        let server = RemoteServer::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut cpu = CPU::new();
+            server.serve_one(&mut cpu).unwrap();
+        });
+
+        let stream = TcpStream::connect(address).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut request = |command: &str| {
+            writeln!(writer, "{}", command).unwrap();
+            let mut answer = String::new();
+            reader.read_line(&mut answer).unwrap();
+            answer.trim_end().to_string()
+        };
+
+        // I = font "0", draw it at (0, 0)
+        assert_eq!(request("load oFDQBQ=="), "ok");
+        assert_eq!(request("step"), "ok");
+        assert_eq!(request("step"), "ok");
+
+        let screen = request("screen");
+        let mut parts = screen.split(' ');
+        assert_eq!(parts.next(), Some("ok"));
+        assert_eq!(parts.next(), Some("64"));
+        assert_eq!(parts.next(), Some("32"));
+        let pixels = parts.next().unwrap();
+        assert_eq!(pixels.len(), 64 * 32);
+        assert!(pixels.starts_with("11110000"));
+        assert!(pixels[64..].starts_with("10010000"));
+
+        writeln!(writer, "quit").unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_wait_for_key_over_loopback() {
+        let server = RemoteServer::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut cpu = CPU::new();
+            server.serve_one(&mut cpu).unwrap();
+            (
+                cpu.registers().read(0).unwrap(),
+                cpu.program_counter(),
+                cpu.blocking_key_wait(),
+            )
+        });
+
+        let stream = TcpStream::connect(address).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut request = |command: &str| {
+            writeln!(writer, "{}", command).unwrap();
+            let mut answer = String::new();
+            reader.read_line(&mut answer).unwrap();
+            answer.trim_end().to_string()
+        };
+
+        // LD V0, K
+        assert_eq!(request("load 8Ao="), "ok");
+        assert_eq!(request("step"), "ok");
+        assert_eq!(request("step"), "ok");
+        assert_eq!(request("keys 0x0020"), "ok");
+        assert_eq!(request("step"), "ok");
+
+        writeln!(writer, "quit").unwrap();
+        // V0 holds the key, the CPU moved past Fx0A and blocking is back on.
+        assert_eq!(handle.join().unwrap(), (5, 0x202, true));
+    }
+}
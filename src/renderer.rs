@@ -0,0 +1,349 @@
+use log::trace;
+
+use crate::screen::{Screen, ScreenMode, COLLUMNS, ROWS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+impl Color {
+    pub const BLACK: Color = Color::rgb(0x00, 0x00, 0x00);
+    pub const WHITE: Color = Color::rgb(0xFF, 0xFF, 0xFF);
+    pub const AMBER: Color = Color::rgb(0xFF, 0xB0, 0x00);
+    pub const GREEN_PHOSPHOR: Color = Color::rgb(0x33, 0xFF, 0x33);
+    pub const GRAY: Color = Color::rgb(0x80, 0x80, 0x80);
+    pub const DARK_GRAY: Color = Color::rgb(0x40, 0x40, 0x40);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+
+    /// Packs the color as `0x00RRGGBB`.
+    pub fn to_u32(self) -> u32 {
+        (self.r as u32) << 16 | (self.g as u32) << 8 | self.b as u32
+    }
+}
+
+/// Colors of the four pixel states. Monochrome programs only ever draw to plane 0 and so only
+/// show `foreground` and `background`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// Set on plane 0 only.
+    pub foreground: Color,
+    /// Set on no plane.
+    pub background: Color,
+    /// Set on plane 1 only.
+    pub second_plane: Color,
+    /// Set on both planes.
+    pub both_planes: Color,
+}
+impl Palette {
+    /// A palette with gray for the XO-CHIP plane colors.
+    pub fn new(foreground: Color, background: Color) -> Self {
+        Palette::with_planes(foreground, background, Color::GRAY, Color::DARK_GRAY)
+    }
+
+    pub fn with_planes(
+        foreground: Color,
+        background: Color,
+        second_plane: Color,
+        both_planes: Color,
+    ) -> Self {
+        Palette {
+            foreground,
+            background,
+            second_plane,
+            both_planes,
+        }
+    }
+
+    pub fn amber() -> Self {
+        Palette::new(Color::AMBER, Color::BLACK)
+    }
+
+    pub fn green_phosphor() -> Self {
+        Palette::new(Color::GREEN_PHOSPHOR, Color::BLACK)
+    }
+
+    /// Returns the color of a set or unset pixel.
+    pub fn color(&self, pixel: bool) -> Color {
+        if pixel {
+            self.foreground
+        } else {
+            self.background
+        }
+    }
+
+    /// Returns the color of a 2-bit pixel value with plane 0 in the lowest bit, as yielded by
+    /// `Screen::iter_plane_pixels`.
+    pub fn plane_color(&self, value: u8) -> Color {
+        match value & 0b11 {
+            0b00 => self.background,
+            0b01 => self.foreground,
+            0b10 => self.second_plane,
+            _ => self.both_planes,
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::new(Color::WHITE, Color::BLACK)
+    }
+}
+
+/// Default number of window pixels per CHIP-8 pixel.
+pub const DEFAULT_SCALE: usize = 10;
+
+/// Turns the `Screen` into a `0x00RRGGBB` frame buffer for a front-end to present.
+/// Every CHIP-8 pixel becomes a `scale` x `scale` block, so the image stays crisp.
+#[derive(Debug)]
+pub struct Renderer {
+    palette: Palette,
+    scale: usize,
+    // Render lo-res screens at the hi-res size, see `set_fixed_hires`.
+    fixed_hires: bool,
+    frame: Vec<u32>,
+
+    // Forces the next render after the palette or scale changed.
+    stale: bool,
+}
+impl Renderer {
+    pub fn new(palette: Palette) -> Self {
+        Renderer {
+            palette,
+            scale: DEFAULT_SCALE,
+            fixed_hires: false,
+            frame: vec![palette.background.to_u32(); COLLUMNS * ROWS * DEFAULT_SCALE.pow(2)],
+            stale: true,
+        }
+    }
+
+    pub fn scale(&self) -> usize {
+        self.scale
+    }
+
+    /// Changes the scale. A scale of 0 is treated as 1.
+    pub fn set_scale(&mut self, scale: usize) {
+        trace!("Set render scale to {}", scale);
+        self.scale = scale.max(1);
+        self.stale = true;
+    }
+
+    pub fn fixed_hires(&self) -> bool {
+        self.fixed_hires
+    }
+
+    /// Renders lo-res screens into a hi-res sized frame, every lo-res pixel becoming a 2x2 block
+    /// of hi-res pixels. This is how SCHIP looks on the HP48, whose display is always 128x64,
+    /// and keeps the window size from jumping when a program switches modes. Off by default,
+    /// which renders lo-res screens at half the size instead.
+    pub fn set_fixed_hires(&mut self, fixed_hires: bool) {
+        trace!("Set fixed hi-res rendering to {}", fixed_hires);
+        self.fixed_hires = fixed_hires;
+        self.stale = true;
+    }
+
+    /// Returns the size of the rendered frame in pixels as `(width, height)`.
+    pub fn window_size(&self, screen: &Screen) -> (usize, usize) {
+        let pixel_size = self.pixel_size(screen);
+        (screen.width() * pixel_size, screen.height() * pixel_size)
+    }
+
+    /// Frame pixels per side of a screen pixel.
+    fn pixel_size(&self, screen: &Screen) -> usize {
+        if self.fixed_hires && screen.mode() == ScreenMode::LoRes {
+            self.scale * 2
+        } else {
+            self.scale
+        }
+    }
+
+    pub fn palette(&self) -> Palette {
+        self.palette
+    }
+
+    /// Changes the palette. Takes effect on the next `render`.
+    pub fn set_palette(&mut self, palette: Palette) {
+        trace!("Set palette to {:?}", palette);
+        self.palette = palette;
+        self.stale = true;
+    }
+
+    /// The last rendered frame, row by row.
+    pub fn frame(&self) -> &[u32] {
+        &self.frame
+    }
+
+    /// Maps every pixel of `screen` to its palette color if the screen changed since the last
+    /// render. Returns false if the frame was left untouched.
+    pub fn render(&mut self, screen: &mut Screen) -> bool {
+        if !screen.is_dirty() && !self.stale {
+            trace!("Screen unchanged, skipping render.");
+            return false;
+        };
+
+        let (width, height) = self.window_size(screen);
+        let pixel_size = self.pixel_size(screen);
+        self.frame.resize(width * height, 0);
+
+        for (index, value) in screen.iter_plane_pixels().enumerate() {
+            let color = self.palette.plane_color(value).to_u32();
+            let x = (index % screen.width()) * pixel_size;
+            let y = (index / screen.width()) * pixel_size;
+
+            for row in y..y + pixel_size {
+                self.frame[row * width + x..row * width + x + pixel_size].fill(color);
+            }
+        }
+
+        screen.clear_dirty();
+        self.stale = false;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod renderer_tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_color() {
+        let palette = Palette::default();
+        assert_eq!(palette.color(true), Color::WHITE);
+        assert_eq!(palette.color(false), Color::BLACK);
+
+        let palette = Palette::amber();
+        assert_eq!(palette.color(true), Color::AMBER);
+        assert_eq!(palette.color(false), Color::BLACK);
+    }
+
+    #[test]
+    fn test_render_plane_colors() {
+        let mut screen = Screen::new();
+        let palette = Palette::with_planes(
+            Color::rgb(0x01, 0x01, 0x01),
+            Color::rgb(0x00, 0x00, 0x00),
+            Color::rgb(0x02, 0x02, 0x02),
+            Color::rgb(0x03, 0x03, 0x03),
+        );
+        let mut renderer = Renderer::new(palette);
+        renderer.set_scale(1);
+
+        // Pixel x gets the 2-bit value x.
+        for (x, planes) in [(1, 0b01), (2, 0b10), (3, 0b11)] {
+            screen.select_planes(planes).unwrap();
+            screen.set_pixel(x, 0, true).unwrap();
+        }
+
+        renderer.render(&mut screen);
+        assert_eq!(
+            &renderer.frame()[..4],
+            &[0x000000, 0x010101, 0x020202, 0x030303]
+        );
+        assert_eq!(
+            &screen.to_image_rgba(palette)[..16],
+            &[0, 0, 0, 0xFF, 1, 1, 1, 0xFF, 2, 2, 2, 0xFF, 3, 3, 3, 0xFF]
+        );
+    }
+
+    #[test]
+    fn test_render_uses_palette() {
+        let mut screen = Screen::new();
+        let mut renderer = Renderer::new(Palette::default());
+
+        renderer.render(&mut screen);
+        assert!(renderer
+            .frame()
+            .iter()
+            .all(|&color| color == Color::BLACK.to_u32()));
+
+        renderer.set_palette(Palette::new(
+            Color::GREEN_PHOSPHOR,
+            Color::rgb(0x10, 0x20, 0x30),
+        ));
+        renderer.render(&mut screen);
+        assert!(renderer.frame().iter().all(|&color| color == 0x102030));
+
+        screen.set_pixel(0, 0, true).unwrap();
+        renderer.render(&mut screen);
+        assert_eq!(renderer.frame()[0], Color::GREEN_PHOSPHOR.to_u32());
+        assert_eq!(renderer.frame()[renderer.scale()], 0x102030);
+    }
+
+    #[test]
+    fn test_window_size() {
+        let mut screen = Screen::new();
+        let mut renderer = Renderer::new(Palette::default());
+
+        assert_eq!(
+            renderer.window_size(&screen),
+            (COLLUMNS * DEFAULT_SCALE, ROWS * DEFAULT_SCALE)
+        );
+        renderer.render(&mut screen);
+        assert_eq!(
+            renderer.frame().len(),
+            COLLUMNS * DEFAULT_SCALE * ROWS * DEFAULT_SCALE
+        );
+
+        renderer.set_scale(4);
+        assert_eq!(renderer.window_size(&screen), (COLLUMNS * 4, ROWS * 4));
+        renderer.render(&mut screen);
+        assert_eq!(renderer.frame().len(), COLLUMNS * 4 * ROWS * 4);
+
+        renderer.set_scale(0);
+        assert_eq!(renderer.window_size(&screen), (COLLUMNS, ROWS));
+    }
+
+    #[test]
+    fn test_fixed_hires() {
+        let mut screen = Screen::new();
+        let mut renderer = Renderer::new(Palette::default());
+        renderer.set_scale(1);
+        renderer.set_fixed_hires(true);
+
+        screen.set_pixel(1, 0, true).unwrap();
+        assert_eq!(renderer.window_size(&screen), (COLLUMNS * 2, ROWS * 2));
+        renderer.render(&mut screen);
+
+        // Lo-res pixel (1, 0) covers hi-res pixels (2..4, 0..2).
+        let lit: Vec<(usize, usize)> = (0..renderer.frame().len())
+            .filter(|&index| renderer.frame()[index] == Color::WHITE.to_u32())
+            .map(|index| (index % (COLLUMNS * 2), index / (COLLUMNS * 2)))
+            .collect();
+        assert_eq!(lit, [(2, 0), (3, 0), (2, 1), (3, 1)]);
+
+        // Hi-res pixels stay 1x1, so the frame size does not change.
+        screen.set_mode(ScreenMode::HiRes);
+        screen.set_pixel(1, 0, true).unwrap();
+        renderer.render(&mut screen);
+        assert_eq!(renderer.frame().len(), COLLUMNS * 2 * ROWS * 2);
+        assert_eq!(&renderer.frame()[..3], &[0x000000, 0xFFFFFF, 0x000000]);
+
+        renderer.set_fixed_hires(false);
+        screen.set_mode(ScreenMode::LoRes);
+        assert_eq!(renderer.window_size(&screen), (COLLUMNS, ROWS));
+    }
+
+    #[test]
+    fn test_render_only_when_dirty() {
+        let mut screen = Screen::new();
+        let mut renderer = Renderer::new(Palette::default());
+
+        screen.draw_sprite(0, 0, &[0xFF]);
+        assert!(renderer.render(&mut screen));
+        assert!(!screen.is_dirty());
+        assert!(!renderer.render(&mut screen));
+
+        screen.clear();
+        assert!(renderer.render(&mut screen));
+        assert!(!renderer.render(&mut screen));
+
+        renderer.set_palette(Palette::amber());
+        assert!(renderer.render(&mut screen));
+        assert!(!renderer.render(&mut screen));
+    }
+}
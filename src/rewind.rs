@@ -0,0 +1,69 @@
+use std::collections::VecDeque;
+
+use crate::cpu::CpuState;
+
+/// Ring buffer of `CpuState`s captured every `interval` steps.
+/// Once `capacity` is reached the oldest snapshot is dropped.
+#[derive(Debug)]
+pub struct RewindBuffer {
+    states: VecDeque<CpuState>,
+    capacity: usize,
+    interval: usize,
+    steps_since_capture: usize,
+}
+impl RewindBuffer {
+    pub fn new(capacity: usize, interval: usize) -> Self {
+        RewindBuffer {
+            // Grows as snapshots come in, a large capacity must not reserve all of them up front.
+            states: VecDeque::new(),
+            capacity,
+            interval: interval.max(1),
+            steps_since_capture: 0,
+        }
+    }
+
+    /// Counts a step and returns true if a snapshot should be captured before it.
+    pub fn tick(&mut self) -> bool {
+        let capture = self.steps_since_capture == 0;
+        self.steps_since_capture = (self.steps_since_capture + 1) % self.interval;
+
+        capture
+    }
+
+    pub fn push(&mut self, state: CpuState) {
+        if self.capacity == 0 {
+            return;
+        };
+
+        if self.states.len() == self.capacity {
+            self.states.pop_front();
+        };
+
+        self.states.push_back(state);
+    }
+
+    /// Removes the newest `steps` snapshots and returns the oldest of them.
+    pub fn pop(&mut self, steps: usize) -> Option<CpuState> {
+        if steps == 0 || steps > self.states.len() {
+            return None;
+        };
+
+        self.steps_since_capture = 0;
+
+        self.states.drain(self.states.len() - steps..).next()
+    }
+
+    /// Drops all stored snapshots.
+    pub fn clear(&mut self) {
+        self.states.clear();
+        self.steps_since_capture = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+}
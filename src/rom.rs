@@ -0,0 +1,189 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use log::trace;
+
+/// CHIP-8 dialect or machine a ROM was written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// Plain CHIP-8 on the COSMAC VIP, programs start at 0x200.
+    Chip8,
+    /// The ETI-660, programs start at 0x600.
+    Eti660,
+    /// SUPER-CHIP on the HP48, programs start at 0x200.
+    SuperChip,
+    /// XO-CHIP as implemented by Octo, programs start at 0x200.
+    XoChip,
+}
+impl Platform {
+    /// Address the ROM is loaded at and execution starts from.
+    pub fn program_start(self) -> u16 {
+        match self {
+            Platform::Eti660 => 0x600,
+            Platform::Chip8 | Platform::SuperChip | Platform::XoChip => 0x200,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomReport {
+    /// Number of opcodes only SCHIP (and XO-CHIP) understands.
+    pub super_chip_opcodes: usize,
+    /// Number of opcodes only XO-CHIP understands.
+    pub xo_chip_opcodes: usize,
+    /// The most capable platform whose opcodes were found.
+    pub platform: Platform,
+}
+
+/// Scans `data` for opcodes specific to SCHIP and XO-CHIP and guesses the target platform.
+///
+/// This is best-effort: ROMs mix code and sprite data, so data bytes can look like opcodes
+/// and code at odd addresses is not seen.
+pub fn analyze_rom(data: &[u8]) -> RomReport {
+    let mut super_chip_opcodes = 0;
+    let mut xo_chip_opcodes = 0;
+
+    for word in data.chunks_exact(2) {
+        let opcode = (word[0] as u16) << 8 | word[1] as u16;
+
+        if is_xo_chip_opcode(opcode) {
+            trace!("Found XO-CHIP opcode {:04X}", opcode);
+            xo_chip_opcodes += 1;
+        } else if is_super_chip_opcode(opcode) {
+            trace!("Found SCHIP opcode {:04X}", opcode);
+            super_chip_opcodes += 1;
+        };
+    }
+
+    let platform = if xo_chip_opcodes > 0 {
+        Platform::XoChip
+    } else if super_chip_opcodes > 0 {
+        Platform::SuperChip
+    } else {
+        Platform::Chip8
+    };
+
+    RomReport {
+        super_chip_opcodes,
+        xo_chip_opcodes,
+        platform,
+    }
+}
+
+/// Returns the `.ch8` and gzip-compressed `.ch8.gz` files directly inside `dir`, sorted by path.
+/// The extension is matched case-insensitively and subdirectories are not searched.
+pub fn list_roms<P: AsRef<Path>>(dir: P) -> io::Result<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+
+    for entry in dir.as_ref().read_dir()? {
+        let path = entry?.path();
+
+        let is_rom = path.file_name().is_some_and(|name| {
+            let name = name.to_string_lossy().to_ascii_lowercase();
+            name.ends_with(".ch8") || name.ends_with(".ch8.gz")
+        });
+
+        if is_rom && path.is_file() {
+            trace!("Found ROM {}", path.display());
+            roms.push(path);
+        };
+    }
+
+    roms.sort();
+
+    Ok(roms)
+}
+
+fn is_super_chip_opcode(opcode: u16) -> bool {
+    match opcode & 0xF000 {
+        // 00Cn scroll down, 00FB scroll right, 00FC scroll left, 00FD exit, 00FE low-res, 00FF high-res
+        0x0000 => opcode & 0xFFF0 == 0x00C0 || (0x00FB..=0x00FF).contains(&opcode),
+        // Dxy0 draws a 16x16 sprite
+        0xD000 => opcode & 0xF == 0,
+        // Fx30 hi-res font, Fx75/Fx85 RPL user flags
+        0xF000 => matches!(opcode & 0xFF, 0x30 | 0x75 | 0x85),
+        _ => false,
+    }
+}
+
+fn is_xo_chip_opcode(opcode: u16) -> bool {
+    match opcode & 0xF000 {
+        // 00Dn scroll up
+        0x0000 => opcode & 0xFFF0 == 0x00D0,
+        // 5xy2/5xy3 save/load register range
+        0x5000 => matches!(opcode & 0xF, 0x2 | 0x3),
+        // F000 long I, Fn01 plane select, F002 audio pattern, Fx3A pitch
+        0xF000 => opcode == 0xF000 || opcode == 0xF002 || matches!(opcode & 0xFF, 0x01 | 0x3A),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod rom_tests {
+    use super::*;
+
+    #[test]
+    fn test_chip_8_rom() {
+        // CLS, V0 = 1, draw 5 byte sprite, jump to self
+        let report = analyze_rom(&[0x00, 0xE0, 0x60, 0x01, 0xD0, 0x15, 0x12, 0x06]);
+
+        assert_eq!(report.platform, Platform::Chip8);
+        assert_eq!(report.super_chip_opcodes, 0);
+        assert_eq!(report.xo_chip_opcodes, 0);
+    }
+
+    #[test]
+    fn test_super_chip_rom() {
+        // high-res, draw 16x16 sprite, scroll down 4, hi-res font, store flags
+        let report = analyze_rom(&[0x00, 0xFF, 0xD0, 0x10, 0x00, 0xC4, 0xF0, 0x30, 0xF3, 0x75]);
+
+        assert_eq!(report.platform, Platform::SuperChip);
+        assert_eq!(report.super_chip_opcodes, 5);
+        assert_eq!(report.xo_chip_opcodes, 0);
+    }
+
+    #[test]
+    fn test_xo_chip_rom() {
+        // high-res, long I = 0x1234, plane 3, save V1..V4
+        let report = analyze_rom(&[0x00, 0xFF, 0xF0, 0x00, 0x12, 0x34, 0xF3, 0x01, 0x51, 0x42]);
+
+        assert_eq!(report.platform, Platform::XoChip);
+        assert_eq!(report.super_chip_opcodes, 1);
+        assert_eq!(report.xo_chip_opcodes, 3);
+    }
+
+    #[test]
+    fn test_odd_length_rom() {
+        assert_eq!(
+            analyze_rom(&[0x00, 0xFF, 0xF0]).platform,
+            Platform::SuperChip
+        );
+        assert_eq!(analyze_rom(&[]).platform, Platform::Chip8);
+    }
+
+    #[test]
+    fn test_list_roms() {
+        let dir = std::env::temp_dir().join(format!("chip_8_roms_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested.ch8")).unwrap();
+        for name in ["pong.ch8", "Brix.CH8", "ufo.ch8.gz", "readme.txt", "tetris"] {
+            std::fs::write(dir.join(name), [0x00, 0xE0]).unwrap();
+        }
+
+        let roms = list_roms(&dir);
+        let empty = list_roms(dir.join("nested.ch8"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            roms.unwrap(),
+            [
+                dir.join("Brix.CH8"),
+                dir.join("pong.ch8"),
+                dir.join("ufo.ch8.gz")
+            ]
+        );
+        assert!(empty.unwrap().is_empty());
+        assert!(list_roms(&dir).is_err());
+    }
+}
@@ -1,30 +1,912 @@
-use log::trace;
+use std::{io, path::Path};
 
-const COLLUMNS: usize = 64;
-const ROWS: usize = 32;
+use image::{ImageFormat, Rgba, RgbaImage};
+use log::{debug, info, trace};
 
-#[derive(Debug)]
+use crate::renderer::{Palette, DEFAULT_SCALE};
+
+pub const COLLUMNS: usize = 64;
+pub const ROWS: usize = 32;
+/// Number of bit-planes, XO-CHIP draws to one or both of them.
+pub const PLANES: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenError {
+    /// The pixel lies outside the current resolution.
+    OutOfBounds { x: usize, y: usize },
+    /// The plane mask selects a plane that does not exist.
+    InvalidPlanes(u8),
+}
+
+/// Display resolution. SCHIP switches between them with 00FE and 00FF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScreenMode {
+    /// 64x32, the original CHIP-8 resolution.
+    #[default]
+    LoRes,
+    /// 128x64, the SCHIP extended resolution.
+    HiRes,
+}
+impl ScreenMode {
+    pub fn width(self) -> usize {
+        match self {
+            ScreenMode::LoRes => COLLUMNS,
+            ScreenMode::HiRes => COLLUMNS * 2,
+        }
+    }
+
+    pub fn height(self) -> usize {
+        match self {
+            ScreenMode::LoRes => ROWS,
+            ScreenMode::HiRes => ROWS * 2,
+        }
+    }
+}
+
+/// Copy of the display content, taken with `Screen::capture`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenBuffer {
+    mode: ScreenMode,
+    planes: [Vec<u128>; PLANES],
+}
+impl ScreenBuffer {
+    pub fn mode(&self) -> ScreenMode {
+        self.mode
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Screen {
-    screen: [u8; 64 * 32],
+    mode: ScreenMode,
+
+    // One u128 per row and plane, only the lowest `width` bits are used and the most significant
+    // of them is the leftmost pixel.
+    planes: [Vec<u128>; PLANES],
+
+    // Bit n set means drawing and clearing affect plane n.
+    selected_planes: u8,
+
+    // Set whenever the content changes, cleared by the renderer.
+    dirty: bool,
+
+    // Log every draw at debug level.
+    draw_logging: bool,
+
+    // Cut sprites off at the edges instead of wrapping them around.
+    clip_sprites: bool,
 }
 
 impl Screen {
     pub fn new() -> Self {
         Screen {
-            screen: [0u8; COLLUMNS * ROWS],
+            mode: ScreenMode::LoRes,
+            planes: [vec![0u128; ROWS], vec![0u128; ROWS]],
+            selected_planes: 0b01,
+            dirty: true,
+            draw_logging: false,
+            clip_sprites: false,
         }
     }
 
+    /// Clears the selected planes.
     pub fn clear(&mut self) {
-        self.screen = [0u8; COLLUMNS * ROWS]
+        for plane in self.selected_plane_indices() {
+            self.planes[plane].fill(0);
+        }
+        self.dirty = true;
+    }
+
+    /// Flips every pixel of the selected planes, e.g. for transition effects.
+    pub fn invert(&mut self) {
+        let row_mask = self.row_mask();
+
+        for plane in self.selected_plane_indices() {
+            for row in self.planes[plane].iter_mut() {
+                *row ^= row_mask;
+            }
+        }
+        self.dirty = true;
+    }
+
+    pub fn mode(&self) -> ScreenMode {
+        self.mode
+    }
+
+    /// Switches the resolution. Like SCHIP, the content is cleared rather than scaled.
+    pub fn set_mode(&mut self, mode: ScreenMode) {
+        trace!("Switching screen to {:?}", mode);
+
+        self.mode = mode;
+        self.planes = [vec![0u128; mode.height()], vec![0u128; mode.height()]];
+        self.dirty = true;
+    }
+
+    /// Bitmask of the planes drawing and clearing affect, plane 0 being the lowest bit.
+    pub fn selected_planes(&self) -> u8 {
+        self.selected_planes
+    }
+
+    /// Selects the planes for drawing and clearing like XO-CHIP's Fn01. Bit n of `mask` selects
+    /// plane n, so 0 selects no plane and 3 both. Fails with `ScreenError::InvalidPlanes` if a
+    /// higher bit is set, leaving the selection unchanged.
+    pub fn select_planes(&mut self, mask: u8) -> Result<(), ScreenError> {
+        if mask >> PLANES != 0 {
+            return Err(ScreenError::InvalidPlanes(mask));
+        };
+
+        trace!("Selecting planes {:02b}", mask);
+        self.selected_planes = mask;
+
+        Ok(())
+    }
+
+    /// Number of selected planes, a sprite holds one set of rows for each of them.
+    pub fn selected_plane_count(&self) -> usize {
+        self.selected_planes.count_ones() as usize
+    }
+
+    /// XORs `sprite` onto the selected planes with its top left corner at (`x`, `y`).
+    /// Every byte is one row. With two planes selected the first half of `sprite` goes to plane 0
+    /// and the second half to plane 1.
+    ///
+    /// The origin always wraps, so it is (`x % width`, `y % height`) of the current resolution
+    /// whether or not sprites are clipped. Only the pixels that then leave the screen either wrap
+    /// around to the other side or are dropped if `clip_sprites` is set. Clipping the origin too
+    /// is a common bug that makes sprites at e.g. x = 70 disappear.
+    ///
+    /// Returns true if a set pixel got erased on any plane. Collision is tracked per plane: a
+    /// pixel that is set on plane 0 only does not collide with the plane 1 half of a sprite, so
+    /// a sprite can collide in one plane but not the other. The results are ORed into a single
+    /// flag, which the CPU writes to V(0xF) once. Planes that are not selected never collide.
+    pub fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        trace!("Drawing {} byte sprite at ({}, {})", sprite.len(), x, y);
+
+        let height = self.height();
+        // Wrapped independently of `clip_sprites`.
+        let x = x as usize % self.width();
+        let y = y as usize % height;
+
+        let plane_rows = (sprite.len() / self.selected_plane_count().max(1)).max(1);
+        // Clipped sprites end at the bottom edge.
+        let visible_rows = if self.clip_sprites {
+            plane_rows.min(height - y)
+        } else {
+            plane_rows
+        };
+
+        let mut collision = false;
+        for (plane, rows) in self.selected_plane_indices().zip(sprite.chunks(plane_rows)) {
+            collision |= self.draw_plane(plane, x, y, &rows[..visible_rows.min(rows.len())]);
+        }
+
+        self.dirty = true;
+
+        if self.draw_logging {
+            let rows: Vec<usize> = (0..visible_rows)
+                .map(|offset| (y + offset) % height)
+                .collect();
+            debug!(
+                "Drew sprite {:02X?} at ({}, {}) on rows {:?}, collision {}",
+                sprite, x, y, rows, collision
+            );
+        };
+
+        collision
+    }
+
+    /// XORs `rows` onto `plane` at the already wrapped (`x`, `y`).
+    fn draw_plane(&mut self, plane: usize, x: usize, y: usize, rows: &[u8]) -> bool {
+        let width = self.width();
+        let height = self.height();
+        let row_mask = self.row_mask();
+
+        let mut collision = false;
+
+        for (offset, &byte) in rows.iter().enumerate() {
+            let row = &mut self.planes[plane][(y + offset) % height];
+
+            // Rotate right by x within `width` bits, or only shift when clipping.
+            let sprite_row = (byte as u128) << (width - 8);
+            let wrapped = if self.clip_sprites {
+                0
+            } else {
+                sprite_row.checked_shl((width - x) as u32).unwrap_or(0)
+            };
+            let mask = (sprite_row >> x | wrapped) & row_mask;
+
+            collision |= *row & mask != 0;
+            *row ^= mask;
+        }
+
+        collision
+    }
+
+    pub fn draw_logging(&self) -> bool {
+        self.draw_logging
+    }
+
+    /// Makes `draw` log the sprite bytes, the wrapped position, the rows it touched and whether
+    /// it collided at debug level. Meant for tracking down garbled graphics without enabling the
+    /// global trace.
+    pub fn set_draw_logging(&mut self, enabled: bool) {
+        info!("Setting draw logging to {}", enabled);
+        self.draw_logging = enabled;
     }
 
-    pub fn draw(&mut self) {
-        //todo!("Draw sprite onto screen.");
-        trace!("Fake Drawing!");
+    pub fn clip_sprites(&self) -> bool {
+        self.clip_sprites
     }
 
-    pub fn render(&mut self) {
-        todo!("Add screen render capabilities.")
+    /// Makes `draw_sprite` cut sprites off at the right and bottom edges instead of wrapping
+    /// them around, like the COSMAC VIP and SCHIP. The CPU sets this from its quirks.
+    pub fn set_clip_sprites(&mut self, enabled: bool) {
+        trace!("Setting sprite clipping to {}", enabled);
+        self.clip_sprites = enabled;
+    }
+
+    /// Copies the resolution and pixels, e.g. to diff them later or to undo a change.
+    pub fn capture(&self) -> ScreenBuffer {
+        ScreenBuffer {
+            mode: self.mode,
+            planes: self.planes.clone(),
+        }
+    }
+
+    /// Shows a previously captured buffer again, including its resolution.
+    pub fn load(&mut self, buffer: &ScreenBuffer) {
+        trace!("Loading {:?} screen buffer", buffer.mode);
+
+        self.mode = buffer.mode;
+        self.planes.clone_from(&buffer.planes);
+        self.dirty = true;
+    }
+
+    /// Returns true if the screen changed since the last `clear_dirty`.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty = false;
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn width(&self) -> usize {
+        self.mode.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.mode.height()
+    }
+
+    /// Returns whether the pixel at (`x`, `y`) is set on any plane, or None if it is off screen.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<bool> {
+        self.get_plane_pixel(x, y).map(|value| value != 0)
+    }
+
+    /// Returns the pixel at (`x`, `y`) as a 2-bit value with plane 0 in the lowest bit, or None
+    /// if it is off screen.
+    pub fn get_plane_pixel(&self, x: usize, y: usize) -> Option<u8> {
+        let (row, _) = self.index(x, y)?;
+
+        Some(self.plane_value(self.planes[0][row], self.planes[1][row], x))
+    }
+
+    /// Sets or unsets the pixel at (`x`, `y`) on the selected planes. Fails with
+    /// `ScreenError::OutOfBounds` if it is off screen.
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) -> Result<(), ScreenError> {
+        let (row, mask) = self.index(x, y).ok_or(ScreenError::OutOfBounds { x, y })?;
+
+        for plane in self.selected_plane_indices() {
+            let row = &mut self.planes[plane][row];
+
+            if on {
+                *row |= mask;
+            } else {
+                *row &= !mask;
+            };
+        }
+
+        self.dirty = true;
+
+        Ok(())
+    }
+
+    /// Iterates over all pixels row by row, `true` meaning the pixel is set on any plane.
+    pub fn iter_pixels(&self) -> impl Iterator<Item = bool> + '_ {
+        self.iter_plane_pixels().map(|value| value != 0)
+    }
+
+    /// Iterates over all pixels row by row as 2-bit values with plane 0 in the lowest bit,
+    /// e.g. to look up their color with `Palette::plane_color`.
+    pub fn iter_plane_pixels(&self) -> impl Iterator<Item = u8> + '_ {
+        self.planes[0]
+            .iter()
+            .zip(&self.planes[1])
+            .flat_map(move |(&first, &second)| {
+                (0..self.width()).map(move |column| self.plane_value(first, second, column))
+            })
+    }
+
+    /// All pixels row by row, `width() * height()` of them.
+    pub fn pixels(&self) -> Vec<bool> {
+        self.iter_pixels().collect()
+    }
+
+    /// Iterates over row `y` from left to right, `true` meaning the pixel is set on any plane.
+    /// Returns None if the row is off screen.
+    pub fn row(&self, y: usize) -> Option<impl Iterator<Item = bool> + '_> {
+        if y >= self.height() {
+            return None;
+        };
+
+        let row = self.planes[0][y] | self.planes[1][y];
+        Some((0..self.width()).map(move |x| row & self.column_bit(x) != 0))
+    }
+
+    /// The screen shrunk by `scale` for previews such as a ROM picker, row by row. Every pixel
+    /// covers a `scale` x `scale` block and is set if any pixel of the block is, so thin lines
+    /// do not vanish. Blocks at the right and bottom edges may be cut off. A scale of 0 is
+    /// treated as 1.
+    pub fn thumbnail(&self, scale: usize) -> Vec<bool> {
+        let scale = scale.max(1);
+        let width = self.width().div_ceil(scale);
+        let height = self.height().div_ceil(scale);
+
+        let mut thumbnail = vec![false; width * height];
+        for (index, pixel) in self.iter_pixels().enumerate() {
+            let x = index % self.width();
+            let y = index / self.width();
+            thumbnail[y / scale * width + x / scale] |= pixel;
+        }
+
+        thumbnail
+    }
+
+    /// The screen as RGBA bytes, row by row with 4 bytes per pixel, e.g. to build a texture or
+    /// save a PNG.
+    pub fn to_image_rgba(&self, palette: Palette) -> Vec<u8> {
+        self.iter_plane_pixels()
+            .flat_map(|value| {
+                let color = palette.plane_color(value);
+                [color.r, color.g, color.b, 0xFF]
+            })
+            .collect()
+    }
+
+    /// Saves the screen as a PNG, every pixel scaled up to a `DEFAULT_SCALE` sized square.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P, palette: Palette) -> io::Result<()> {
+        info!("Saving screenshot to {}", path.as_ref().display());
+
+        let image = RgbaImage::from_fn(
+            (self.width() * DEFAULT_SCALE) as u32,
+            (self.height() * DEFAULT_SCALE) as u32,
+            |x, y| {
+                let value = self
+                    .get_plane_pixel(x as usize / DEFAULT_SCALE, y as usize / DEFAULT_SCALE)
+                    .unwrap_or(0);
+                let color = palette.plane_color(value);
+
+                Rgba([color.r, color.g, color.b, 0xFF])
+            },
+        );
+
+        image
+            .save_with_format(path, ImageFormat::Png)
+            .map_err(io::Error::other)
+    }
+
+    /// Indices of the selected planes in ascending order.
+    fn selected_plane_indices(&self) -> impl Iterator<Item = usize> {
+        let selected_planes = self.selected_planes;
+        (0..PLANES).filter(move |plane| selected_planes & (1 << plane) != 0)
+    }
+
+    /// Combines column `x` of a row on both planes into a 2-bit value.
+    fn plane_value(&self, first: u128, second: u128, x: usize) -> u8 {
+        let bit = self.column_bit(x);
+        (first & bit != 0) as u8 | ((second & bit != 0) as u8) << 1
+    }
+
+    /// Row and column bit of the pixel at (`x`, `y`), or None if it lies outside the current
+    /// resolution. Every pixel accessor goes through this, so a stale hi-res coordinate after
+    /// switching back to lo-res can not index past the visible screen.
+    fn index(&self, x: usize, y: usize) -> Option<(usize, u128)> {
+        if x >= self.width() || y >= self.height() {
+            return None;
+        };
+
+        Some((y, self.column_bit(x)))
+    }
+
+    /// Bit of column `x` in a row.
+    fn column_bit(&self, x: usize) -> u128 {
+        1 << (self.width() - 1 - x)
+    }
+
+    /// Bits of a row that are on screen.
+    fn row_mask(&self) -> u128 {
+        u128::MAX >> (128 - self.width())
+    }
+}
+
+// Two screens are equal if they show the same pixels, whether or not they were rendered yet.
+impl PartialEq for Screen {
+    fn eq(&self, other: &Self) -> bool {
+        self.mode == other.mode && self.planes == other.planes
+    }
+}
+
+#[cfg(test)]
+mod screen_tests {
+    use super::*;
+    use crate::renderer::Color;
+
+    /// Byte per pixel reference implementation of `Screen::draw`.
+    fn draw_bytes(pixels: &mut [u8], x: u8, y: u8, sprite: &[u8]) -> bool {
+        let mut collision = false;
+
+        for (row, &byte) in sprite.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) == 0 {
+                    continue;
+                };
+
+                let px = (x as usize + bit) % COLLUMNS;
+                let py = (y as usize + row) % ROWS;
+                let pixel = &mut pixels[py * COLLUMNS + px];
+
+                collision |= *pixel == 1;
+                *pixel ^= 1;
+            }
+        }
+
+        collision
+    }
+
+    #[test]
+    fn test_draw_matches_byte_buffer() {
+        let mut screen = Screen::new();
+        let mut pixels = [0u8; COLLUMNS * ROWS];
+
+        let draws: [(u8, u8, &[u8]); 6] = [
+            (0, 0, &[0xF0, 0x90, 0x90, 0x90, 0xF0]),
+            (2, 2, &[0xFF, 0x81]),
+            (60, 30, &[0xAA, 0x55, 0xAA, 0x55]),
+            (63, 0, &[0xC3]),
+            (100, 40, &[0x3C]),
+            (0, 0, &[0xF0, 0x90, 0x90, 0x90, 0xF0]),
+        ];
+
+        for (x, y, sprite) in draws {
+            assert_eq!(
+                screen.draw_sprite(x, y, sprite),
+                draw_bytes(&mut pixels, x, y, sprite)
+            );
+            assert!(screen
+                .iter_pixels()
+                .eq(pixels.iter().map(|&pixel| pixel == 1)));
+        }
+    }
+
+    #[test]
+    fn test_draw_collision() {
+        let mut screen = Screen::new();
+
+        assert!(!screen.draw_sprite(10, 10, &[0x80]));
+        assert_eq!(screen.get_pixel(10, 10), Some(true));
+
+        // Drawing the same sprite again erases it.
+        assert!(screen.draw_sprite(10, 10, &[0x80]));
+        assert!(screen.iter_pixels().all(|pixel| !pixel));
+    }
+
+    #[test]
+    fn test_draw_sprite_clipping() {
+        let mut screen = Screen::new();
+        screen.set_clip_sprites(true);
+
+        assert!(!screen.draw_sprite(60, 30, &[0xFF, 0xFF, 0xFF]));
+
+        for x in 60..64 {
+            assert_eq!(screen.get_pixel(x, 30), Some(true));
+            assert_eq!(screen.get_pixel(x, 31), Some(true));
+        }
+        // Nothing wrapped to the left edge or the top.
+        assert_eq!(screen.iter_pixels().filter(|&pixel| pixel).count(), 8);
+
+        // Only the visible part can collide.
+        assert!(!screen.draw_sprite(0, 0, &[0xFF]));
+        assert!(screen.draw_sprite(62, 31, &[0xC0]));
+    }
+
+    #[test]
+    fn test_draw_sprite_origin_wraps() {
+        for clip_sprites in [false, true] {
+            let mut screen = Screen::new();
+            screen.set_clip_sprites(clip_sprites);
+
+            // The origin always wraps, only the pixels past the edge are clipped.
+            screen.draw_sprite(COLLUMNS as u8 + 2, ROWS as u8 + 3, &[0x80]);
+            assert_eq!(screen.get_pixel(2, 3), Some(true));
+            assert_eq!(screen.iter_pixels().filter(|&pixel| pixel).count(), 1);
+
+            // 255 wraps to the last column, so only the first pixel of a clipped row is left.
+            screen.clear();
+            screen.draw_sprite(255, 255, &[0xC0]);
+            assert_eq!(screen.get_pixel(COLLUMNS - 1, ROWS - 1), Some(true));
+            assert_eq!(screen.get_pixel(0, ROWS - 1), Some(!clip_sprites));
+
+            // The same coordinates wrap by the hi-res size after switching.
+            screen.set_mode(ScreenMode::HiRes);
+            screen.draw_sprite(130, 70, &[0x80]);
+            assert_eq!(screen.get_pixel(2, 6), Some(true));
+            screen.draw_sprite(255, 255, &[0x80]);
+            assert_eq!(screen.get_pixel(127, 63), Some(true));
+            assert_eq!(screen.iter_pixels().filter(|&pixel| pixel).count(), 2);
+        }
+    }
+
+    #[test]
+    fn test_draw_sprite_planes() {
+        let mut screen = Screen::new();
+
+        screen.select_planes(0b10).unwrap();
+        assert!(!screen.draw_sprite(0, 0, &[0xC0]));
+        assert_eq!(screen.get_plane_pixel(0, 0), Some(0b10));
+
+        // Both planes take one row each, the collision comes from plane 1.
+        screen.select_planes(0b11).unwrap();
+        assert!(screen.draw_sprite(0, 0, &[0x80, 0x80]));
+        assert_eq!(screen.get_plane_pixel(0, 0), Some(0b01));
+        assert_eq!(screen.get_plane_pixel(1, 0), Some(0b10));
+        assert_eq!(screen.get_plane_pixel(0, 1), Some(0b00));
+
+        assert_eq!(
+            screen.select_planes(0b111),
+            Err(ScreenError::InvalidPlanes(0b111))
+        );
+        assert_eq!(screen.selected_planes(), 0b11);
+
+        // No plane selected draws nothing.
+        screen.select_planes(0).unwrap();
+        assert!(!screen.draw_sprite(0, 0, &[0xFF]));
+
+        screen.select_planes(0b01).unwrap();
+        screen.clear();
+        assert_eq!(screen.get_plane_pixel(0, 0), Some(0b00));
+        assert_eq!(screen.get_plane_pixel(1, 0), Some(0b10));
+    }
+
+    #[test]
+    fn test_draw_sprite_plane_collision() {
+        // (pixels already set on plane 0, on plane 1, expected collision)
+        let cases = [
+            (0x80, 0x00, true),
+            (0x00, 0x80, true),
+            (0x80, 0x80, true),
+            (0x40, 0x40, false),
+        ];
+
+        for (plane_0, plane_1, collision) in cases {
+            let mut screen = Screen::new();
+            screen.select_planes(0b01).unwrap();
+            screen.draw_sprite(0, 0, &[plane_0]);
+            screen.select_planes(0b10).unwrap();
+            screen.draw_sprite(0, 0, &[plane_1]);
+
+            screen.select_planes(0b11).unwrap();
+            assert_eq!(screen.draw_sprite(0, 0, &[0x80, 0x80]), collision);
+        }
+
+        // A pixel on an unselected plane does not collide.
+        let mut screen = Screen::new();
+        screen.select_planes(0b10).unwrap();
+        screen.draw_sprite(0, 0, &[0x80]);
+        screen.select_planes(0b01).unwrap();
+        assert!(!screen.draw_sprite(0, 0, &[0x80]));
+        assert_eq!(screen.get_plane_pixel(0, 0), Some(0b11));
+    }
+
+    #[test]
+    fn test_invert() {
+        let mut screen = Screen::new();
+        screen.set_mode(ScreenMode::HiRes);
+        screen.draw_sprite(3, 4, &[0xF0, 0x90, 0xF0]);
+        let buffer = screen.capture();
+
+        screen.clear_dirty();
+        screen.invert();
+        assert!(screen.is_dirty());
+        assert_eq!(screen.get_pixel(3, 4), Some(false));
+        assert_eq!(screen.get_pixel(0, 0), Some(true));
+        assert_eq!(
+            screen.iter_pixels().filter(|&pixel| pixel).count(),
+            128 * 64 - 10
+        );
+
+        screen.invert();
+        assert_eq!(screen.capture(), buffer);
+    }
+
+    #[test]
+    fn test_get_set_pixel() {
+        let mut screen = Screen::new();
+
+        assert_eq!(screen.get_pixel(0, 0), Some(false));
+        assert_eq!(screen.set_pixel(0, 0, true), Ok(()));
+        assert_eq!(screen.get_pixel(0, 0), Some(true));
+
+        assert_eq!(screen.set_pixel(COLLUMNS - 1, ROWS - 1, true), Ok(()));
+        assert_eq!(screen.get_pixel(COLLUMNS - 1, ROWS - 1), Some(true));
+        assert_eq!(screen.get_pixel(COLLUMNS - 2, ROWS - 1), Some(false));
+
+        assert_eq!(screen.set_pixel(0, 0, false), Ok(()));
+        assert_eq!(screen.get_pixel(0, 0), Some(false));
+
+        // Collision with a pixel set by hand.
+        assert!(screen.draw_sprite(COLLUMNS as u8 - 1, ROWS as u8 - 1, &[0x80]));
+    }
+
+    #[test]
+    fn test_get_set_pixel_out_of_bounds() {
+        let mut screen = Screen::new();
+
+        assert_eq!(screen.get_pixel(COLLUMNS, 0), None);
+        assert_eq!(screen.get_pixel(0, ROWS), None);
+        assert_eq!(
+            screen.set_pixel(COLLUMNS, 0, true),
+            Err(ScreenError::OutOfBounds { x: COLLUMNS, y: 0 })
+        );
+        assert_eq!(
+            screen.set_pixel(0, ROWS, true),
+            Err(ScreenError::OutOfBounds { x: 0, y: ROWS })
+        );
+        assert!(screen.iter_pixels().all(|pixel| !pixel));
+    }
+
+    #[test]
+    fn test_index() {
+        let mut screen = Screen::new();
+
+        for mode in [ScreenMode::LoRes, ScreenMode::HiRes] {
+            screen.set_mode(mode);
+            let (width, height) = (screen.width(), screen.height());
+
+            assert_eq!(screen.index(0, 0), Some((0, 1 << (width - 1))));
+            assert_eq!(screen.index(width - 1, height - 1), Some((height - 1, 1)));
+            assert_eq!(screen.index(width, 0), None);
+            assert_eq!(screen.index(0, height), None);
+            assert_eq!(screen.index(usize::MAX, usize::MAX), None);
+        }
+
+        // Valid in hi-res only.
+        assert_eq!(screen.index(100, 40), Some((40, 1 << 27)));
+        screen.set_mode(ScreenMode::LoRes);
+        assert_eq!(screen.index(100, 40), None);
+        assert_eq!(
+            screen.set_pixel(100, 40, true),
+            Err(ScreenError::OutOfBounds { x: 100, y: 40 })
+        );
+        assert_eq!(screen.get_plane_pixel(100, 40), None);
+    }
+
+    #[test]
+    fn test_set_mode() {
+        let mut screen = Screen::new();
+        assert_eq!(screen.mode(), ScreenMode::LoRes);
+        assert_eq!((screen.width(), screen.height()), (64, 32));
+
+        screen.set_pixel(10, 10, true).unwrap();
+        screen.clear_dirty();
+        screen.set_mode(ScreenMode::HiRes);
+
+        assert!(screen.is_dirty());
+        assert_eq!((screen.width(), screen.height()), (128, 64));
+        assert_eq!(screen.iter_pixels().count(), 128 * 64);
+        assert!(screen.iter_pixels().all(|pixel| !pixel));
+        assert_eq!(screen.get_pixel(127, 63), Some(false));
+        assert_eq!(screen.get_pixel(128, 0), None);
+
+        screen.set_pixel(127, 63, true).unwrap();
+        screen.set_mode(ScreenMode::LoRes);
+
+        assert_eq!((screen.width(), screen.height()), (64, 32));
+        assert!(screen.iter_pixels().all(|pixel| !pixel));
+        assert_eq!(screen.get_pixel(64, 0), None);
+    }
+
+    #[test]
+    fn test_hires_draw_wraps() {
+        let mut screen = Screen::new();
+        screen.set_mode(ScreenMode::HiRes);
+
+        assert!(!screen.draw_sprite(124, 63, &[0xFF, 0x81]));
+
+        for x in 124..128 {
+            assert_eq!(screen.get_pixel(x, 63), Some(true));
+        }
+        for x in 0..4 {
+            assert_eq!(screen.get_pixel(x, 63), Some(true));
+        }
+        assert_eq!(screen.get_pixel(124, 0), Some(true));
+        assert_eq!(screen.get_pixel(3, 0), Some(true));
+        assert_eq!(screen.get_pixel(125, 0), Some(false));
+        assert_eq!(screen.iter_pixels().filter(|&pixel| pixel).count(), 10);
+    }
+
+    #[test]
+    fn test_capture_load() {
+        let mut screen = Screen::new();
+        screen.draw_sprite(3, 4, &[0xF0, 0x90, 0xF0]);
+        let buffer = screen.capture();
+
+        screen.clear();
+        assert_ne!(screen.capture(), buffer);
+
+        screen.set_mode(ScreenMode::HiRes);
+        screen.clear_dirty();
+        screen.load(&buffer);
+
+        assert!(screen.is_dirty());
+        assert_eq!(screen.mode(), ScreenMode::LoRes);
+        assert_eq!(screen.capture(), buffer);
+        assert_eq!(screen.get_pixel(3, 4), Some(true));
+        assert_eq!(screen.get_pixel(4, 5), Some(false));
+    }
+
+    #[test]
+    fn test_pixels() {
+        let mut screen = Screen::new();
+        screen.set_pixel(1, 0, true).unwrap();
+
+        let pixels = screen.pixels();
+        assert_eq!(pixels.len(), COLLUMNS * ROWS);
+        assert_eq!(pixels[..3], [false, true, false]);
+
+        screen.set_mode(ScreenMode::HiRes);
+        screen.set_pixel(0, 1, true).unwrap();
+
+        let pixels = screen.pixels();
+        assert_eq!(pixels.len(), 128 * 64);
+        assert!(pixels[128]);
+    }
+
+    #[test]
+    fn test_row() {
+        let mut screen = Screen::new();
+        screen.draw_sprite(60, 3, &[0xA5]);
+
+        let row: Vec<bool> = screen.row(3).unwrap().collect();
+        assert_eq!(row.len(), COLLUMNS);
+        assert_eq!(&row[60..], &[true, false, true, false]);
+        // The sprite wrapped around.
+        assert_eq!(&row[..4], &[false, true, false, true]);
+        assert!(screen.row(4).unwrap().all(|pixel| !pixel));
+        assert!(screen.row(ROWS).is_none());
+
+        // Pixels on the second plane count too.
+        screen.select_planes(0b10).unwrap();
+        screen.set_pixel(5, 4, true).unwrap();
+        assert_eq!(screen.row(4).unwrap().position(|pixel| pixel), Some(5));
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        let mut screen = Screen::new();
+        // Lights the 2x2 blocks (0, 0) and (1, 1) with one pixel each and block (3, 0) fully.
+        screen.set_pixel(1, 0, true).unwrap();
+        screen.set_pixel(2, 3, true).unwrap();
+        screen.draw_sprite(6, 0, &[0xC0, 0xC0]);
+
+        let thumbnail = screen.thumbnail(2);
+        assert_eq!(thumbnail.len(), (COLLUMNS / 2) * (ROWS / 2));
+        let lit: Vec<usize> = (0..thumbnail.len()).filter(|&i| thumbnail[i]).collect();
+        assert_eq!(lit, [0, 3, COLLUMNS / 2 + 1]);
+
+        // Partial blocks at the edges are kept.
+        assert_eq!(screen.thumbnail(5).len(), 13 * 7);
+        assert_eq!(screen.thumbnail(0), screen.pixels());
+    }
+
+    #[test]
+    fn test_to_image_rgba() {
+        let mut screen = Screen::new();
+        screen.set_pixel(1, 0, true).unwrap();
+
+        let palette = Palette::new(Color::rgb(0x11, 0x22, 0x33), Color::rgb(0x44, 0x55, 0x66));
+        let image = screen.to_image_rgba(palette);
+
+        assert_eq!(image.len(), COLLUMNS * ROWS * 4);
+        assert_eq!(image[..4], [0x44, 0x55, 0x66, 0xFF]);
+        assert_eq!(image[4..8], [0x11, 0x22, 0x33, 0xFF]);
+        assert_eq!(image[8..12], [0x44, 0x55, 0x66, 0xFF]);
+    }
+
+    #[test]
+    fn test_save_png() {
+        let path = std::env::temp_dir().join(format!("chip_8_screen_{}.png", std::process::id()));
+
+        let mut screen = Screen::new();
+        screen.set_pixel(1, 0, true).unwrap();
+        let palette = Palette::amber();
+
+        let result = screen.save_png(&path, palette);
+        let image = image::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+        let image = image.unwrap().to_rgba8();
+
+        assert_eq!(
+            image.dimensions(),
+            (
+                (COLLUMNS * DEFAULT_SCALE) as u32,
+                (ROWS * DEFAULT_SCALE) as u32
+            )
+        );
+        let scale = DEFAULT_SCALE as u32;
+        assert_eq!(image.get_pixel(scale, 0), &Rgba([0xFF, 0xB0, 0x00, 0xFF]));
+        assert_eq!(
+            image.get_pixel(2 * scale - 1, scale - 1),
+            &Rgba([0xFF, 0xB0, 0x00, 0xFF])
+        );
+        assert_eq!(image.get_pixel(0, 0), &Rgba([0x00, 0x00, 0x00, 0xFF]));
+        assert_eq!(
+            image.get_pixel(2 * scale, 0),
+            &Rgba([0x00, 0x00, 0x00, 0xFF])
+        );
+    }
+
+    /// Collects the messages of all log records so tests can look for them.
+    struct CaptureLogger {
+        messages: std::sync::Mutex<Vec<String>>,
+    }
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Debug
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                self.messages
+                    .lock()
+                    .unwrap_or_else(|p| p.into_inner())
+                    .push(record.args().to_string());
+            };
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CaptureLogger = CaptureLogger {
+        messages: std::sync::Mutex::new(Vec::new()),
+    };
+
+    #[test]
+    fn test_draw_logging() {
+        log::set_logger(&LOGGER).expect("Only this test may set the logger!");
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let logged = |needle: &str| {
+            LOGGER
+                .messages
+                .lock()
+                .unwrap_or_else(|p| p.into_inner())
+                .iter()
+                .any(|message| message.contains(needle))
+        };
+
+        let mut screen = Screen::new();
+        screen.draw_sprite(1, 2, &[0xA5, 0x5A]);
+        assert!(!logged("[A5, 5A]"));
+
+        screen.set_draw_logging(true);
+        screen.draw_sprite(65, 31, &[0xC3, 0x3C]);
+        assert!(logged(
+            "Drew sprite [C3, 3C] at (1, 31) on rows [31, 0], collision false"
+        ));
     }
 }
@@ -0,0 +1,264 @@
+use std::f32::consts::TAU;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+impl Waveform {
+    /// Returns the amplitude in `-1.0..=1.0` at `phase`, where one period spans `0.0..1.0`.
+    pub fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.
+                } else {
+                    -1.
+                }
+            }
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Triangle => 1. - 4. * (phase - 0.5).abs(),
+        }
+    }
+}
+
+/// Tone that is played while the sound timer is active.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoundConfig {
+    pub frequency_hz: f32,
+    pub waveform: Waveform,
+    /// Amplitude from 0.0 (silent) to 1.0 (full), applied to the beep and to XO-CHIP patterns.
+    /// Values outside that range are clamped.
+    pub volume: f32,
+}
+impl SoundConfig {
+    /// A config playing at full volume.
+    pub fn new(frequency_hz: f32, waveform: Waveform) -> Self {
+        SoundConfig {
+            frequency_hz,
+            waveform,
+            volume: 1.,
+        }
+    }
+}
+
+impl Default for SoundConfig {
+    fn default() -> Self {
+        SoundConfig::new(440., Waveform::Square)
+    }
+}
+
+/// Endless stream of samples for an audio backend running at `sample_rate` Hz.
+#[derive(Debug, Clone)]
+pub struct Tone {
+    config: SoundConfig,
+    sample_rate: u32,
+    index: u64,
+}
+impl Tone {
+    pub fn new(config: SoundConfig, sample_rate: u32) -> Self {
+        Tone {
+            config,
+            sample_rate,
+            index: 0,
+        }
+    }
+}
+
+impl Iterator for Tone {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        // Derive the phase from the sample index so rounding errors do not accumulate.
+        let phase =
+            (self.index as f64 * self.config.frequency_hz as f64 / self.sample_rate as f64).fract();
+        self.index += 1;
+
+        Some(self.config.waveform.sample(phase as f32) * self.config.volume.clamp(0., 1.))
+    }
+}
+
+/// Bytes in an XO-CHIP audio pattern, played as 128 one-bit samples.
+pub const PATTERN_SIZE: usize = 16;
+
+/// Pitch at which a pattern plays at its base rate of 4000 bits per second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// XO-CHIP's programmable sound, loaded with F002 and tuned with Fx3A. It replaces the beep
+/// while the sound timer is active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioPattern {
+    pub buffer: [u8; PATTERN_SIZE],
+    pub pitch: u8,
+}
+impl AudioPattern {
+    pub fn new(buffer: [u8; PATTERN_SIZE]) -> Self {
+        AudioPattern {
+            buffer,
+            pitch: DEFAULT_PITCH,
+        }
+    }
+
+    /// Bits played per second, 4000 * 2^((pitch - 64) / 48) as in Octo.
+    pub fn playback_rate(&self) -> f32 {
+        4000. * 2f32.powf((self.pitch as f32 - DEFAULT_PITCH as f32) / 48.)
+    }
+
+    /// Returns bit `index` of the pattern, the most significant bit of the first byte being 0.
+    pub fn bit(&self, index: usize) -> bool {
+        let index = index % (PATTERN_SIZE * 8);
+        self.buffer[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+
+    /// Endless stream of samples for an audio backend running at `sample_rate` Hz.
+    pub fn samples(self, sample_rate: u32) -> PatternSamples {
+        PatternSamples {
+            pattern: self,
+            sample_rate,
+            volume: 1.,
+            index: 0,
+        }
+    }
+}
+
+/// Plays an `AudioPattern` on repeat, every bit being a sample of `volume` or -`volume`.
+#[derive(Debug, Clone)]
+pub struct PatternSamples {
+    pattern: AudioPattern,
+    sample_rate: u32,
+    volume: f32,
+    index: u64,
+}
+impl PatternSamples {
+    /// Scales the samples by `volume`, clamped to 0.0..=1.0.
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume.clamp(0., 1.);
+        self
+    }
+}
+
+impl Iterator for PatternSamples {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let bit = (self.index as f64 * self.pattern.playback_rate() as f64
+            / self.sample_rate as f64) as u64;
+        self.index += 1;
+
+        Some(if self.pattern.bit(bit as usize) {
+            self.volume
+        } else {
+            -self.volume
+        })
+    }
+}
+
+#[cfg(test)]
+mod sound_tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44_000;
+
+    // 440 Hz at 44 kHz is exactly 100 samples per period.
+    const PERIOD: usize = 100;
+
+    fn samples(waveform: Waveform) -> Vec<f32> {
+        Tone::new(SoundConfig::new(440., waveform), SAMPLE_RATE)
+            .take(PERIOD * 3)
+            .collect()
+    }
+
+    fn assert_periodic(samples: &[f32]) {
+        for (a, b) in samples.iter().zip(&samples[PERIOD..]) {
+            assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_default_config() {
+        assert_eq!(
+            SoundConfig::default(),
+            SoundConfig::new(440., Waveform::Square)
+        );
+    }
+
+    #[test]
+    fn test_square() {
+        let samples = samples(Waveform::Square);
+        assert_periodic(&samples);
+
+        assert!(samples[..PERIOD / 2].iter().all(|&s| s == 1.));
+        assert!(samples[PERIOD / 2 + 1..PERIOD].iter().all(|&s| s == -1.));
+    }
+
+    #[test]
+    fn test_sine() {
+        let samples = samples(Waveform::Sine);
+        assert_periodic(&samples);
+
+        assert!(samples[0].abs() < 1e-3);
+        assert!((samples[PERIOD / 4] - 1.).abs() < 1e-3);
+        assert!((samples[PERIOD * 3 / 4] + 1.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_pattern_samples() {
+        let mut buffer = [0u8; PATTERN_SIZE];
+        buffer[0] = 0b1010_0000;
+        buffer[PATTERN_SIZE - 1] = 0x01;
+        let pattern = AudioPattern::new(buffer);
+
+        assert_eq!(pattern.playback_rate(), 4000.);
+        assert!(pattern.bit(0) && !pattern.bit(1) && pattern.bit(2));
+        assert!(pattern.bit(127) && pattern.bit(128));
+
+        // At 8 kHz every bit lasts two samples.
+        let samples: Vec<f32> = pattern.samples(8000).take(8).collect();
+        assert_eq!(samples, [1., 1., -1., -1., 1., 1., -1., -1.]);
+
+        let samples: Vec<f32> = pattern.samples(8000).skip(254).take(4).collect();
+        assert_eq!(samples, [1., 1., 1., 1.]);
+
+        let higher = AudioPattern {
+            pitch: DEFAULT_PITCH + 48,
+            ..pattern
+        };
+        assert_eq!(higher.playback_rate(), 8000.);
+    }
+
+    #[test]
+    fn test_volume() {
+        let config = SoundConfig {
+            volume: 0.5,
+            ..SoundConfig::default()
+        };
+        let samples: Vec<f32> = Tone::new(config, SAMPLE_RATE).take(PERIOD).collect();
+        assert_eq!(samples[0], 0.5);
+        assert_eq!(samples[PERIOD - 1], -0.5);
+
+        // Out of range volumes are clamped.
+        let config = SoundConfig {
+            volume: 2.,
+            ..SoundConfig::default()
+        };
+        assert_eq!(Tone::new(config, SAMPLE_RATE).next(), Some(1.));
+
+        let pattern = AudioPattern::new([0xF0; PATTERN_SIZE]);
+        let samples: Vec<f32> = pattern.samples(4000).with_volume(0.25).take(8).collect();
+        assert_eq!(
+            samples,
+            [0.25, 0.25, 0.25, 0.25, -0.25, -0.25, -0.25, -0.25]
+        );
+    }
+
+    #[test]
+    fn test_triangle() {
+        let samples = samples(Waveform::Triangle);
+        assert_periodic(&samples);
+
+        assert!((samples[0] + 1.).abs() < 1e-3);
+        assert!((samples[PERIOD / 2] - 1.).abs() < 1e-3);
+        assert!(samples[PERIOD / 4].abs() < 1e-3);
+    }
+}
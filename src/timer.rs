@@ -1,87 +1,146 @@
-use std::{
-    sync::{Arc, Mutex},
-    thread,
-    time::{Duration, Instant},
-};
+use crate::sound::{AudioPattern, PatternSamples, SoundConfig, Tone, DEFAULT_PITCH, PATTERN_SIZE};
 
+/// Counts down at 60 Hz and beeps while it is above zero.
+#[derive(Debug, Clone)]
 pub struct SoundTimer {
-    value: Arc<Mutex<u8>>,
+    value: u8,
+    config: SoundConfig,
+    // Set once a program loads an XO-CHIP pattern.
+    pattern: Option<AudioPattern>,
+    pitch: u8,
+    muted: bool,
 }
 impl SoundTimer {
     pub fn new() -> Self {
+        Self::with_config(SoundConfig::default())
+    }
+
+    pub fn with_config(config: SoundConfig) -> Self {
         Self {
-            value: Arc::new(Mutex::new(0)),
+            value: 0,
+            config,
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+            muted: false,
         }
     }
 
-    pub fn write(&self, value: u8) {
-        let value_c = self.value.clone();
+    pub fn config(&self) -> SoundConfig {
+        self.config
+    }
 
-        let mut value_lock = self.value.lock().unwrap_or_else(|p| p.into_inner());
-        *value_lock = value;
+    pub fn set_config(&mut self, config: SoundConfig) {
+        self.config = config;
+    }
 
-        if *value_lock > 0 {
-            thread::spawn(move || {
-                decrement60hz(value_c);
-            });
-        };
+    /// Returns the beep to play while the timer is active, for an audio backend at `sample_rate` Hz.
+    /// It is silent while muted.
+    pub fn tone(&self, sample_rate: u32) -> Tone {
+        Tone::new(
+            SoundConfig {
+                volume: self.volume(),
+                ..self.config
+            },
+            sample_rate,
+        )
     }
 
-    pub fn read(&self) -> u8 {
-        let value_lock = self.value.lock().unwrap_or_else(|p| p.into_inner());
-        *value_lock
+    pub fn is_muted(&self) -> bool {
+        self.muted
     }
-}
 
-#[derive(Debug)]
-pub struct DelayTimer {
-    value: Arc<Mutex<u8>>,
-}
-impl DelayTimer {
-    pub fn new() -> Self {
-        Self {
-            value: Arc::new(Mutex::new(0)),
+    /// Silences `tone` and `pattern_samples` without touching the configured volume.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// Mutes or unmutes, meant to be bound to a key by the front-end.
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+
+    /// The volume actually played, 0.0 while muted.
+    pub fn volume(&self) -> f32 {
+        if self.muted {
+            0.
+        } else {
+            self.config.volume.clamp(0., 1.)
         }
     }
 
-    pub fn write(&self, value: u8) {
-        let value_c = self.value.clone();
+    pub fn pattern(&self) -> Option<AudioPattern> {
+        self.pattern
+    }
 
-        let mut value_lock = self.value.lock().unwrap_or_else(|p| p.into_inner());
-        *value_lock = value;
+    /// Plays `buffer` instead of the beep from now on, like XO-CHIP's F002.
+    pub fn load_pattern(&mut self, buffer: [u8; PATTERN_SIZE]) {
+        self.pattern = Some(AudioPattern {
+            buffer,
+            pitch: self.pitch,
+        });
+    }
 
-        if *value_lock > 0 {
-            thread::spawn(move || {
-                decrement60hz(value_c);
-            });
+    /// Sets the pitch of the pattern, like XO-CHIP's Fx3A. It is kept for patterns loaded later.
+    pub fn set_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
+        if let Some(pattern) = self.pattern.as_mut() {
+            pattern.pitch = pitch;
         };
     }
 
+    /// Goes back to the beep.
+    pub fn clear_pattern(&mut self) {
+        self.pattern = None;
+        self.pitch = DEFAULT_PITCH;
+    }
+
+    /// Returns the pattern's samples to play while the timer is active, or None if no pattern
+    /// was loaded and `tone` should be played.
+    pub fn pattern_samples(&self, sample_rate: u32) -> Option<PatternSamples> {
+        self.pattern
+            .map(|pattern| pattern.samples(sample_rate).with_volume(self.volume()))
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.value = value;
+    }
+
     pub fn read(&self) -> u8 {
-        let value_lock = self.value.lock().unwrap_or_else(|p| p.into_inner());
-        *value_lock
+        self.value
     }
-}
 
-fn decrement60hz(value: Arc<Mutex<u8>>) {
-    let target_duration = Duration::from_secs_f64(1. / 60.); // 60Hz
+    /// Counts down by one, to be called at 60 Hz.
+    pub fn tick(&mut self) {
+        self.value = self.value.saturating_sub(1);
+    }
 
-    loop {
-        let start = Instant::now();
+    /// Returns true while the beep should be heard, e.g. to light up a sound indicator.
+    pub fn is_active(&self) -> bool {
+        self.value > 0
+    }
+}
 
-        {
-            let mut value_lock = value.lock().unwrap_or_else(|p| p.into_inner());
+/// Counts down at 60 Hz, programs read it for timing.
+#[derive(Debug, Clone)]
+pub struct DelayTimer {
+    value: u8,
+}
+impl DelayTimer {
+    pub fn new() -> Self {
+        Self { value: 0 }
+    }
 
-            if *value_lock > 0 {
-                *value_lock -= 1;
-            } else {
-                break;
-            };
-        }
+    pub fn write(&mut self, value: u8) {
+        self.value = value;
+    }
 
-        if let Some(sleep_duration) = target_duration.checked_sub(start.elapsed()) {
-            thread::sleep(sleep_duration);
-        };
+    pub fn read(&self) -> u8 {
+        self.value
+    }
+
+    /// Counts down by one, to be called at 60 Hz.
+    pub fn tick(&mut self) {
+        self.value = self.value.saturating_sub(1);
     }
 }
 
@@ -91,31 +150,84 @@ mod timer_tests {
 
     #[test]
     fn test_sound_timer() {
-        let sound_timer = SoundTimer::new();
+        let mut sound_timer = SoundTimer::new();
 
         assert_eq!(sound_timer.read(), 0);
 
         sound_timer.write(60);
-        thread::sleep(Duration::from_secs(1));
+        for _ in 0..59 {
+            sound_timer.tick();
+        }
+        assert_eq!(sound_timer.read(), 1);
 
+        sound_timer.tick();
         assert_eq!(sound_timer.read(), 0);
 
-        sound_timer.write(60);
-        assert_ne!(sound_timer.read(), 0);
+        sound_timer.tick();
+        assert_eq!(sound_timer.read(), 0);
+    }
+
+    #[test]
+    fn test_sound_timer_volume() {
+        let mut sound_timer = SoundTimer::with_config(SoundConfig {
+            volume: 0.,
+            ..SoundConfig::default()
+        });
+        sound_timer.write(10);
+        assert!(sound_timer.is_active());
+        assert!(sound_timer.tone(8000).take(100).all(|sample| sample == 0.));
+
+        let mut config = sound_timer.config();
+        config.volume = 0.8;
+        sound_timer.set_config(config);
+        assert_eq!(sound_timer.tone(8000).next(), Some(0.8));
+
+        sound_timer.toggle_mute();
+        assert!(sound_timer.is_muted());
+        assert!(sound_timer.tone(8000).take(100).all(|sample| sample == 0.));
+        sound_timer.load_pattern([0xFF; PATTERN_SIZE]);
+        assert!(sound_timer
+            .pattern_samples(8000)
+            .unwrap()
+            .take(100)
+            .all(|sample| sample == 0.));
+
+        sound_timer.toggle_mute();
+        assert_eq!(sound_timer.volume(), 0.8);
+        assert_eq!(sound_timer.pattern_samples(8000).unwrap().next(), Some(0.8));
+    }
+
+    #[test]
+    fn test_sound_timer_is_active() {
+        let mut sound_timer = SoundTimer::new();
+        assert!(!sound_timer.is_active());
+
+        sound_timer.write(2);
+        assert!(sound_timer.is_active());
+
+        sound_timer.tick();
+        assert!(sound_timer.is_active());
+        sound_timer.tick();
+        assert!(!sound_timer.is_active());
+
+        sound_timer.write(1);
+        sound_timer.write(0);
+        assert!(!sound_timer.is_active());
     }
 
     #[test]
     fn test_delay_timer() {
-        let delay_timer = DelayTimer::new();
+        let mut delay_timer = DelayTimer::new();
 
         assert_eq!(delay_timer.read(), 0);
 
         delay_timer.write(60);
-        thread::sleep(Duration::from_secs(1));
-
+        for _ in 0..60 {
+            delay_timer.tick();
+        }
         assert_eq!(delay_timer.read(), 0);
 
-        delay_timer.write(60);
-        assert_ne!(delay_timer.read(), 0);
+        delay_timer.tick();
+        assert_eq!(delay_timer.read(), 0);
     }
 }
@@ -0,0 +1,69 @@
+use std::collections::VecDeque;
+
+use crate::{
+    ram::Stack,
+    registers::{I, V},
+    screen::Screen,
+};
+
+/// State needed to revert a single instruction.
+///
+/// Registers and the stack are small and always copied. RAM and the screen are only copied
+/// for the instructions that can change them, and for RAM only the bytes that get written.
+#[derive(Debug, Clone)]
+pub struct UndoEntry {
+    pub(crate) program_counter: u16,
+    pub(crate) v: V,
+    pub(crate) i: I,
+    pub(crate) stack: Stack,
+    pub(crate) rpl: [u8; 16],
+    /// Start address and previous content of the RAM the instruction writes to.
+    pub(crate) memory: Option<(u16, Vec<u8>)>,
+    pub(crate) screen: Option<Screen>,
+}
+
+/// Bounded stack of `UndoEntry`s. Once `depth` entries are stored the oldest one is dropped,
+/// so at most `depth` instructions can be stepped back.
+#[derive(Debug)]
+pub struct UndoStack {
+    entries: VecDeque<UndoEntry>,
+    depth: usize,
+}
+impl UndoStack {
+    pub fn new(depth: usize) -> Self {
+        UndoStack {
+            // Grows as entries come in, `push` enforces the depth.
+            entries: VecDeque::new(),
+            depth,
+        }
+    }
+
+    pub fn push(&mut self, entry: UndoEntry) {
+        if self.depth == 0 {
+            return;
+        };
+
+        if self.entries.len() == self.depth {
+            self.entries.pop_front();
+        };
+
+        self.entries.push_back(entry);
+    }
+
+    pub fn pop(&mut self) -> Option<UndoEntry> {
+        self.entries.pop_back()
+    }
+
+    /// Drops all stored entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,118 @@
+//! Runs every ROM in `tests/roms` headlessly and compares the final screen against a reference.
+//!
+//! Each `<name>.ch8` needs a `<name>.expected` next to it with `key = value` lines:
+//!
+//! - `cycles`: number of instructions to run before the screen is compared.
+//! - `quirks`: optional preset name as understood by `Quirks::from_name`.
+//! - `hash`: FNV-1a hash of the screen. On a mismatch the actual screen is printed as ASCII art
+//!   together with its hash, so a new reference can be added by running the suite once.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chip_8_emulator::{cpu::CPU, quirks::Quirks, rom, screen::Screen};
+
+const ROM_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/roms");
+
+struct Reference {
+    cycles: usize,
+    quirks: Quirks,
+    hash: u64,
+}
+
+fn read_reference(rom: &Path) -> Reference {
+    let path = rom.with_extension("expected");
+    let content = fs::read_to_string(&path)
+        .unwrap_or_else(|error| panic!("Could not read {}: {}", path.display(), error));
+
+    let values: HashMap<&str, &str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect();
+
+    let cycles = values
+        .get("cycles")
+        .and_then(|cycles| cycles.parse().ok())
+        .unwrap_or_else(|| panic!("{} needs a cycles count!", path.display()));
+    let quirks = values.get("quirks").map_or(Quirks::default(), |name| {
+        Quirks::from_name(name).unwrap_or_else(|| panic!("Unknown quirk preset {}!", name))
+    });
+    let hash = values
+        .get("hash")
+        .and_then(|hash| u64::from_str_radix(hash.trim_start_matches("0x"), 16).ok())
+        .unwrap_or_else(|| panic!("{} needs a hex hash!", path.display()));
+
+    Reference {
+        cycles,
+        quirks,
+        hash,
+    }
+}
+
+/// 64-bit FNV-1a over the resolution and every pixel, stable across platforms and compilers.
+fn screen_hash(screen: &Screen) -> u64 {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+
+    let size = [screen.width() as u8, screen.height() as u8];
+    let pixels = screen.iter_pixels().map(u8::from);
+
+    for byte in size.into_iter().chain(pixels) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+
+    hash
+}
+
+fn screen_ascii(screen: &Screen) -> String {
+    screen
+        .pixels()
+        .chunks(screen.width())
+        .map(|row| {
+            row.iter()
+                .map(|&pixel| if pixel { '#' } else { '.' })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn run_rom(path: &PathBuf) -> Result<(), String> {
+    let reference = read_reference(path);
+
+    let mut cpu = CPU::with_seed(0);
+    cpu.set_quirks(reference.quirks);
+    cpu.load_rom_from_path(path)
+        .map_err(|error| format!("{}: could not load: {:?}", path.display(), error))?;
+    cpu.run_cycles(reference.cycles)
+        .map_err(|error| format!("{}: CPU failed: {:?}", path.display(), error))?;
+
+    let hash = screen_hash(cpu.screen());
+    if hash != reference.hash {
+        return Err(format!(
+            "{}: expected hash {:#018x}, got {:#018x}\n{}",
+            path.display(),
+            reference.hash,
+            hash,
+            screen_ascii(cpu.screen())
+        ));
+    };
+
+    Ok(())
+}
+
+#[test]
+fn test_reference_roms() {
+    let roms = rom::list_roms(ROM_DIR).expect("Could not list reference ROMs!");
+    assert!(!roms.is_empty(), "No reference ROMs in {}", ROM_DIR);
+
+    let failures: Vec<String> = roms.iter().filter_map(|rom| run_rom(rom).err()).collect();
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}